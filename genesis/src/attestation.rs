@@ -0,0 +1,165 @@
+//! Validator attestations over a generated genesis
+//!
+//! Validators sign the hash of the PoS genesis block they produced (and
+//! optionally the SHA-256 of their genesis manifest) with the Schnorr signing
+//! key they registered in the PoW chain. Collected attestations are checked
+//! against the keys registered in the genesis config and tallied by the stake
+//! that agrees on each genesis hash.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use nimiq_genesis_builder::config::GenesisConfig;
+use nimiq_hash::Blake2bHash;
+use nimiq_keys::{Address, KeyPair, Signature};
+use nimiq_primitives::coin::Coin;
+
+use crate::{types::Error, validator_stakes};
+
+/// Domain separation tag of the attested message
+const ATTESTATION_DOMAIN: &[u8] = b"nimiq-genesis-attestation";
+
+/// Statement of a validator that it produced a genesis
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Attestation {
+    /// User friendly address of the attesting validator
+    pub validator_address: String,
+    /// Hash of the PoS genesis block
+    pub genesis_hash: Blake2bHash,
+    /// Hex encoded SHA-256 of the genesis manifest, if attested
+    pub manifest_sha256: Option<String>,
+    /// Hex encoded Schnorr signature over the attested message
+    pub signature: String,
+}
+
+impl Attestation {
+    /// Message signed by an attestation
+    fn message(genesis_hash: &Blake2bHash, manifest_sha256: Option<&str>) -> Vec<u8> {
+        let mut message = ATTESTATION_DOMAIN.to_vec();
+        message.extend_from_slice(genesis_hash.as_slice());
+        if let Some(manifest_sha256) = manifest_sha256 {
+            message.extend_from_slice(manifest_sha256.as_bytes());
+        }
+        message
+    }
+
+    /// Attests a genesis with the signing key of the validator registered in
+    /// `genesis_config` with the public key of `key_pair`
+    pub fn sign(
+        genesis_config: &GenesisConfig,
+        key_pair: &KeyPair,
+        genesis_hash: Blake2bHash,
+        manifest_sha256: Option<String>,
+    ) -> Result<Self, Error> {
+        let validator = genesis_config
+            .validators
+            .iter()
+            .find(|validator| validator.signing_key == key_pair.public)
+            .ok_or(Error::UnregisteredSigningKey)?;
+        let signature = key_pair.sign(&Self::message(&genesis_hash, manifest_sha256.as_deref()));
+        Ok(Attestation {
+            validator_address: validator.validator_address.to_user_friendly_address(),
+            genesis_hash,
+            manifest_sha256,
+            signature: hex::encode(signature.to_bytes()),
+        })
+    }
+
+    /// Reads an attestation from a TOML file
+    pub fn read(path: &Path) -> Result<Self, Error> {
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Writes the attestation to a TOML file
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        Ok(fs::write(path, toml::to_string(self)?)?)
+    }
+
+    /// Checks the attestation against the validators registered in
+    /// `genesis_config`, returning the address of the attesting validator
+    pub fn verify(&self, genesis_config: &GenesisConfig) -> Result<Address, Error> {
+        let address = Address::from_user_friendly_address(&self.validator_address)
+            .map_err(|_| Error::InvalidAttestation)?;
+        let validator = genesis_config
+            .validators
+            .iter()
+            .find(|validator| validator.validator_address == address)
+            .ok_or(Error::InvalidAttestation)?;
+        let signature = Signature::from_bytes(&hex::decode(&self.signature)?)
+            .map_err(|_| Error::InvalidAttestation)?;
+        let message = Self::message(&self.genesis_hash, self.manifest_sha256.as_deref());
+        if !validator.signing_key.verify(&signature, &message) {
+            return Err(Error::InvalidAttestation);
+        }
+        Ok(address)
+    }
+}
+
+/// Stake agreeing on a genesis hash
+#[derive(Clone, Debug, Default)]
+pub struct AgreeingStake {
+    /// Validators attesting the genesis hash
+    pub validators: Vec<Address>,
+    /// Total stake of the attesting validators
+    pub stake: Coin,
+}
+
+/// Result of verifying a set of attestations
+#[derive(Clone, Debug, Default)]
+pub struct AttestationReport {
+    /// Total stake of the validators registered in the genesis config
+    pub total_stake: Coin,
+    /// Agreeing stake by genesis hash
+    pub by_hash: BTreeMap<String, AgreeingStake>,
+    /// Attestation files that are invalid or duplicated, along with the reason
+    pub rejected: Vec<(PathBuf, String)>,
+}
+
+/// Verifies attestation files against the validators registered in
+/// `genesis_config` and tallies the stake agreeing on each genesis hash. Each
+/// validator is counted at most once.
+pub fn verify_attestations(genesis_config: &GenesisConfig, files: &[PathBuf]) -> AttestationReport {
+    let stakes = validator_stakes(genesis_config);
+    let mut report = AttestationReport {
+        total_stake: stakes
+            .values()
+            .fold(Coin::ZERO, |total, stake| total + *stake),
+        ..Default::default()
+    };
+    let mut attested: HashMap<Address, PathBuf> = HashMap::new();
+
+    for file in files {
+        let result = Attestation::read(file).and_then(|attestation| {
+            let address = attestation.verify(genesis_config)?;
+            Ok((attestation, address))
+        });
+        let (attestation, address) = match result {
+            Ok(attestation) => attestation,
+            Err(error) => {
+                report.rejected.push((file.clone(), error.to_string()));
+                continue;
+            }
+        };
+        if let Some(previous) = attested.get(&address) {
+            report.rejected.push((
+                file.clone(),
+                format!("Validator already attested in {}", previous.display()),
+            ));
+            continue;
+        }
+        attested.insert(address.clone(), file.clone());
+
+        let agreeing = report
+            .by_hash
+            .entry(attestation.genesis_hash.to_hex())
+            .or_default();
+        agreeing.stake += stakes.get(&address).copied().unwrap_or(Coin::ZERO);
+        agreeing.validators.push(address);
+    }
+    report
+}
@@ -0,0 +1,264 @@
+//! Binary genesis files
+//!
+//! A binary genesis file contains the PoS genesis block and the genesis
+//! accounts tree items in `nimiq-serde` form, so it can be loaded without
+//! building the genesis from its TOML config. The genesis config is not stored
+//! in the file: it is rebuilt from the block and the accounts when converting
+//! back to TOML, and only accepted if it builds the same genesis block.
+//!
+//! The file has the following layout, where all integers are big endian `u32`s
+//! and variable length fields are prefixed by their length:
+//!
+//! - Magic bytes (`BINARY_MAGIC`) and format version (`BINARY_VERSION`).
+//! - The genesis block hash followed by the serialized genesis block.
+//! - The number of accounts tree items and each serialized item.
+//! - The Blake2b checksum of everything before it.
+
+use std::{fs, path::Path};
+
+use nimiq_account::{Account, Staker, Validator};
+use nimiq_block::Block;
+use nimiq_genesis_builder::{
+    config::{
+        GenesisAccount, GenesisConfig, GenesisHTLC, GenesisStaker, GenesisValidator,
+        GenesisVestingContract,
+    },
+    GenesisInfo,
+};
+use nimiq_hash::{Blake2bHash, Blake2bHasher, Hasher};
+use nimiq_keys::Address;
+use nimiq_primitives::{key_nibbles::KeyNibbles, policy::Policy, trie::TrieItem};
+use nimiq_serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::{build_genesis_block, types::Error};
+
+/// Magic bytes at the start of every binary genesis file
+const BINARY_MAGIC: &[u8; 4] = b"NQGB";
+
+/// Version of the binary genesis format
+const BINARY_VERSION: u8 = 2;
+
+/// Key prefix of the validators in the staking contract
+const STAKING_PREFIX_VALIDATOR: u8 = 0;
+
+/// Key prefix of the stakers in the staking contract
+const STAKING_PREFIX_STAKER: u8 = 1;
+
+/// Reader over the contents of a binary genesis file
+struct BinaryReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> BinaryReader<'a> {
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.bytes.len() < len {
+            return Err(Error::InvalidBinaryGenesis);
+        }
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, Error> {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(self.read_bytes(4)?);
+        Ok(u32::from_be_bytes(bytes))
+    }
+
+    fn read_field(&mut self) -> Result<&'a [u8], Error> {
+        let len = self.read_u32()?;
+        self.read_bytes(len as usize)
+    }
+}
+
+fn write_field(bytes: &mut Vec<u8>, field: &[u8]) {
+    bytes.extend_from_slice(&(field.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(field);
+}
+
+/// Writes the PoS genesis block `genesis` to a binary genesis file
+pub fn write_binary_genesis(file_path: &Path, genesis: &GenesisInfo) -> Result<(), Error> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(BINARY_MAGIC);
+    bytes.push(BINARY_VERSION);
+    bytes.extend_from_slice(genesis.hash.as_bytes());
+    write_field(&mut bytes, &genesis.block.serialize_to_vec());
+    bytes.extend_from_slice(&(genesis.accounts.len() as u32).to_be_bytes());
+    for item in &genesis.accounts {
+        write_field(&mut bytes, &item.serialize_to_vec());
+    }
+
+    let checksum: Blake2bHash = Blake2bHasher::default().digest(&bytes);
+    bytes.extend_from_slice(checksum.as_bytes());
+    Ok(fs::write(file_path, bytes)?)
+}
+
+/// Reads the PoS genesis block of a binary genesis file.
+///
+/// The checksum of the file and the hash of the genesis block are verified.
+pub fn read_binary_genesis(file_path: &Path) -> Result<GenesisInfo, Error> {
+    let bytes = fs::read(file_path)?;
+    if bytes.len() < Blake2bHash::SIZE {
+        return Err(Error::InvalidBinaryGenesis);
+    }
+    let (contents, checksum) = bytes.split_at(bytes.len() - Blake2bHash::SIZE);
+    let expected_checksum: Blake2bHash = Blake2bHasher::default().digest(contents);
+    if checksum != expected_checksum.as_bytes() {
+        return Err(Error::BinaryGenesisChecksumMismatch);
+    }
+
+    let mut reader = BinaryReader { bytes: contents };
+    if reader.read_bytes(BINARY_MAGIC.len())? != BINARY_MAGIC
+        || reader.read_bytes(1)?[0] != BINARY_VERSION
+    {
+        return Err(Error::InvalidBinaryGenesis);
+    }
+
+    let mut hash = [0u8; Blake2bHash::SIZE];
+    hash.copy_from_slice(reader.read_bytes(Blake2bHash::SIZE)?);
+    let hash = Blake2bHash::from(hash);
+    let block = Block::deserialize_from_vec(reader.read_field()?)
+        .map_err(|_| Error::InvalidBinaryGenesis)?;
+    if block.hash() != hash {
+        return Err(Error::InvalidBinaryGenesis);
+    }
+
+    let num_accounts = reader.read_u32()?;
+    let mut accounts = Vec::with_capacity(num_accounts as usize);
+    for _ in 0..num_accounts {
+        accounts.push(
+            TrieItem::deserialize_from_vec(reader.read_field()?)
+                .map_err(|_| Error::InvalidBinaryGenesis)?,
+        );
+    }
+    if !reader.bytes.is_empty() {
+        return Err(Error::InvalidBinaryGenesis);
+    }
+
+    Ok(GenesisInfo {
+        block,
+        hash,
+        accounts,
+    })
+}
+
+/// Gets the bytes of the nibbles of `key` in `range`
+fn key_bytes(key: &KeyNibbles, range: std::ops::Range<usize>) -> Result<Vec<u8>, Error> {
+    let nibble = |index| key.get(index).ok_or(Error::InvalidBinaryGenesis);
+    range
+        .step_by(2)
+        .map(|index| Ok(((nibble(index)? << 4) | nibble(index + 1)?) as u8))
+        .collect()
+}
+
+/// Rebuilds the genesis config of a PoS genesis block from its block header
+/// and accounts tree items.
+///
+/// The rebuilt config is only returned if it builds a genesis block with the
+/// same hash as `genesis`.
+pub fn genesis_config_from_binary(genesis: &GenesisInfo) -> Result<GenesisConfig, Error> {
+    let block = &genesis.block;
+    let seed_message =
+        String::from_utf8(block.extra_data().to_vec()).map_err(|_| Error::InvalidBinaryGenesis)?;
+    let mut genesis_config = GenesisConfig {
+        seed_message: (!seed_message.is_empty()).then_some(seed_message),
+        vrf_seed: Some(block.seed().clone()),
+        parent_election_hash: block.parent_election_hash().cloned(),
+        parent_hash: Some(block.parent_hash().clone()),
+        history_root: Some(block.history_root().clone()),
+        block_number: block.block_number(),
+        timestamp: Some(OffsetDateTime::from_unix_timestamp(
+            block.timestamp() as i64
+        )?),
+        validators: vec![],
+        stakers: vec![],
+        basic_accounts: vec![],
+        vesting_accounts: vec![],
+        htlc_accounts: vec![],
+    };
+
+    let staking_prefix = KeyNibbles::from(&Policy::STAKING_CONTRACT_ADDRESS);
+    // Staking contract items are keyed by the contract address, a one byte
+    // prefix and the address of the validator or staker.
+    let staking_item_len = staking_prefix.len() + 2 * (1 + Address::SIZE);
+    for item in &genesis.accounts {
+        if staking_prefix.is_prefix_of(&item.key) {
+            if item.key.len() != staking_item_len {
+                // The staking contract account itself and its indices
+                continue;
+            }
+            let prefix = key_bytes(&item.key, staking_prefix.len()..staking_prefix.len() + 2)?;
+            match prefix[0] {
+                STAKING_PREFIX_VALIDATOR => {
+                    let validator = Validator::deserialize_from_vec(&item.value)
+                        .map_err(|_| Error::InvalidBinaryGenesis)?;
+                    genesis_config.validators.push(GenesisValidator {
+                        validator_address: validator.address,
+                        signing_key: validator.signing_key,
+                        voting_key: validator
+                            .voting_key
+                            .uncompress()
+                            .map_err(|_| Error::InvalidBinaryGenesis)?,
+                        reward_address: validator.reward_address,
+                    });
+                }
+                STAKING_PREFIX_STAKER => {
+                    let staker = Staker::deserialize_from_vec(&item.value)
+                        .map_err(|_| Error::InvalidBinaryGenesis)?;
+                    genesis_config.stakers.push(GenesisStaker {
+                        staker_address: staker.address,
+                        balance: staker.balance,
+                        delegation: staker.delegation.ok_or(Error::InvalidBinaryGenesis)?,
+                    });
+                }
+                _ => return Err(Error::InvalidBinaryGenesis),
+            }
+            continue;
+        }
+
+        if item.key.len() != 2 * Address::SIZE {
+            return Err(Error::InvalidBinaryGenesis);
+        }
+        let address = Address::from(&key_bytes(&item.key, 0..item.key.len())?[..]);
+        match Account::deserialize_from_vec(&item.value).map_err(|_| Error::InvalidBinaryGenesis)? {
+            Account::Basic(account) => genesis_config.basic_accounts.push(GenesisAccount {
+                address,
+                balance: account.balance,
+            }),
+            Account::Vesting(contract) => {
+                genesis_config
+                    .vesting_accounts
+                    .push(GenesisVestingContract {
+                        address,
+                        owner: contract.owner,
+                        balance: contract.balance,
+                        start_time: contract.start_time,
+                        step_amount: contract.step_amount,
+                        time_step: contract.time_step,
+                        total_amount: contract.total_amount,
+                    })
+            }
+            Account::HTLC(contract) => genesis_config.htlc_accounts.push(GenesisHTLC {
+                address,
+                recipient: contract.recipient,
+                sender: contract.sender,
+                balance: contract.balance,
+                hash_root: contract.hash_root,
+                hash_count: contract.hash_count,
+                timeout: contract.timeout,
+                total_amount: contract.total_amount,
+            }),
+            _ => return Err(Error::InvalidBinaryGenesis),
+        }
+    }
+
+    let rebuilt_hash = build_genesis_block(&genesis_config)?.hash;
+    if rebuilt_hash != genesis.hash {
+        return Err(Error::BinaryGenesisConfigMismatch {
+            expected: genesis.hash.clone(),
+            found: rebuilt_hash,
+        });
+    }
+    Ok(genesis_config)
+}
@@ -0,0 +1,163 @@
+//! Field-by-field comparison of genesis configs
+//!
+//! Used to verify a genesis config against one rebuilt from the PoW chain or
+//! a recorded snapshot. Lists such as validators or accounts are matched by
+//! their address, so a difference points at the affected entry.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use nimiq_genesis_builder::config::GenesisConfig;
+
+/// Difference in a field of two genesis configs
+#[derive(Clone, Debug)]
+pub struct FieldDifference {
+    /// Path of the field, e.g. `basic_accounts[NQ07 ...].balance`
+    pub field: String,
+    /// Value in the expected config, if present
+    pub expected: Option<String>,
+    /// Value in the config being verified, if present
+    pub found: Option<String>,
+}
+
+/// Differences between two genesis configs
+#[derive(Clone, Debug, Default)]
+pub struct GenesisDiff {
+    /// Fields that differ
+    pub differences: Vec<FieldDifference>,
+}
+
+impl GenesisDiff {
+    /// Compares the genesis config being verified (`found`) to the `expected` one
+    pub fn compare(expected: &GenesisConfig, found: &GenesisConfig) -> Self {
+        let mut diff = GenesisDiff::default();
+        diff.compare_value("seed_message", &expected.seed_message, &found.seed_message);
+        diff.compare_value("vrf_seed", &expected.vrf_seed, &found.vrf_seed);
+        diff.compare_value(
+            "parent_election_hash",
+            &expected.parent_election_hash,
+            &found.parent_election_hash,
+        );
+        diff.compare_value("parent_hash", &expected.parent_hash, &found.parent_hash);
+        diff.compare_value("history_root", &expected.history_root, &found.history_root);
+        diff.compare_value("block_number", &expected.block_number, &found.block_number);
+        diff.compare_value("timestamp", &expected.timestamp, &found.timestamp);
+        diff.compare_list(
+            "validators",
+            "validator_address",
+            &expected.validators,
+            &found.validators,
+        );
+        diff.compare_list(
+            "stakers",
+            "staker_address",
+            &expected.stakers,
+            &found.stakers,
+        );
+        diff.compare_list(
+            "basic_accounts",
+            "address",
+            &expected.basic_accounts,
+            &found.basic_accounts,
+        );
+        diff.compare_list(
+            "vesting_accounts",
+            "address",
+            &expected.vesting_accounts,
+            &found.vesting_accounts,
+        );
+        diff.compare_list(
+            "htlc_accounts",
+            "address",
+            &expected.htlc_accounts,
+            &found.htlc_accounts,
+        );
+        diff
+    }
+
+    /// Whether both genesis configs are equal
+    pub fn is_empty(&self) -> bool {
+        self.differences.is_empty()
+    }
+
+    fn to_value<T: Serialize>(value: &T) -> Value {
+        serde_json::to_value(value).unwrap_or_else(|error| Value::String(error.to_string()))
+    }
+
+    fn compare_value<T: Serialize>(&mut self, field: &str, expected: &T, found: &T) {
+        self.compare_json(
+            field.to_string(),
+            Self::to_value(expected),
+            Self::to_value(found),
+        );
+    }
+
+    fn compare_json(&mut self, field: String, expected: Value, found: Value) {
+        match (expected, found) {
+            (Value::Object(expected), Value::Object(mut found)) => {
+                for (key, expected) in expected {
+                    let found = found.remove(&key).unwrap_or(Value::Null);
+                    self.compare_json(format!("{}.{}", field, key), expected, found);
+                }
+                for (key, found) in found {
+                    self.compare_json(format!("{}.{}", field, key), Value::Null, found);
+                }
+            }
+            (expected, found) if expected != found => {
+                self.differences.push(FieldDifference {
+                    field,
+                    expected: (!expected.is_null()).then(|| expected.to_string()),
+                    found: (!found.is_null()).then(|| found.to_string()),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    /// Compares two lists whose entries are identified by their `key` field.
+    /// Missing or extra entries are reported as a whole. Keys must be unique,
+    /// so keys appearing more than once in either list are reported and their
+    /// entries are compared in order.
+    fn compare_list<T: Serialize>(&mut self, field: &str, key: &str, expected: &[T], found: &[T]) {
+        let by_key = |entries: &[T]| -> BTreeMap<String, Vec<Value>> {
+            let mut by_key: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+            for value in entries.iter().map(Self::to_value) {
+                let id = match &value[key] {
+                    Value::String(id) => id.clone(),
+                    id => id.to_string(),
+                };
+                by_key.entry(id).or_default().push(value);
+            }
+            by_key
+        };
+        let mut expected = by_key(expected);
+        let mut found = by_key(found);
+        let ids: BTreeSet<String> = expected.keys().chain(found.keys()).cloned().collect();
+
+        for id in ids {
+            let expected = expected.remove(&id).unwrap_or_default();
+            let found = found.remove(&id).unwrap_or_default();
+            if expected.len() > 1 || found.len() > 1 {
+                self.differences.push(FieldDifference {
+                    field: format!("{}[{}]", field, id),
+                    expected: Some(format!("{} entries", expected.len())),
+                    found: Some(format!("{} entries", found.len())),
+                });
+            }
+            for index in 0..expected.len().max(found.len()) {
+                let entry_field = if index == 0 {
+                    format!("{}[{}]", field, id)
+                } else {
+                    format!("{}[{}#{}]", field, id, index)
+                };
+                self.compare_json(
+                    entry_field,
+                    expected.get(index).cloned().unwrap_or(Value::Null),
+                    found.get(index).cloned().unwrap_or(Value::Null),
+                );
+            }
+        }
+    }
+}
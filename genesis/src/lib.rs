@@ -1,51 +1,80 @@
+pub mod attestation;
+pub mod binary;
+pub mod diff;
+pub mod manifest;
+pub mod profile;
+pub mod report;
+pub mod stages;
 pub mod types;
+pub mod vrf;
 
-use std::{fs, str::FromStr, time::Instant};
+use std::{collections::HashMap, fs, str::FromStr, time::Instant};
 
-use nimiq_database::DatabaseProxy;
-use nimiq_genesis_builder::config::GenesisConfig;
+use nimiq_database::volatile::VolatileDatabase;
+use nimiq_genesis_builder::{config::GenesisConfig, GenesisBuilder, GenesisInfo};
 use nimiq_hash::Blake2bHash;
-use nimiq_keys::{KeyPair, SecureGenerate};
-use nimiq_rpc::Client;
-use nimiq_vrf::VrfSeed;
-use rand::{rngs::StdRng, SeedableRng};
+use nimiq_keys::Address;
+use nimiq_primitives::{coin::Coin, networks::NetworkId};
+use nimiq_rpc::{primitives::Block, Client};
 use time::OffsetDateTime;
 
-use nimiq_history_migration::get_history_root;
-use nimiq_state_migration::{get_accounts, get_stakers, get_validators};
+use nimiq_history_migration::{
+    archive::import_history, database::DEFAULT_DB_MAX_TABLES, get_history_root,
+};
+use nimiq_migration_progress::ProgressSubscriber;
+use nimiq_state_migration::{get_accounts, get_stakers, get_validators, VALIDATOR_DEPOSIT};
 
-use crate::types::{Error, PoSRegisteredAgents, PoWRegistrationWindow};
+use crate::{
+    profile::{GenesisProfile, TimestampStrategy},
+    report::GenesisReport,
+    stages::{AccountsStage, AgentsStage, FinalBlockStage, HistoryStage, Stage},
+    types::{Error, HistorySource, PoSRegisteredAgents, PoWRegistrationWindow},
+    vrf::derive_vrf_seed,
+};
 
-// POW estimated block time in milliseconds
-const POW_BLOCK_TIME_MS: u64 = 60 * 1000; // 1 min
-
-/// Gets the genesis config file
-pub async fn get_pos_genesis(
+/// Gets the history root of the PoS genesis block from the given source
+async fn get_genesis_history_root(
     client: &Client,
-    pow_reg_window: &PoWRegistrationWindow,
-    env: DatabaseProxy,
-    pos_registered_agents: Option<PoSRegisteredAgents>,
-) -> Result<GenesisConfig, Error> {
-    // Get block according to arguments and check if it exists
-    let final_block = client
-        .get_block_by_hash(&pow_reg_window.final_block, false)
-        .await
-        .map_err(|_| {
-            log::error!(
-                hash = pow_reg_window.final_block,
-                "Could not find provided block"
+    cutting_pow_block_number: u32,
+    history: HistorySource,
+    pow_network_id: NetworkId,
+    progress: &dyn ProgressSubscriber,
+) -> Result<Blake2bHash, Error> {
+    let env = match history {
+        HistorySource::Precomputed(history_root) => {
+            log::info!(
+                history_root = history_root.to_hex(),
+                "Using precomputed history root"
             );
-            Error::UnknownBlock
-        })?;
-    let pow_genesis = client.get_block_by_number(1, false).await?;
+            return Ok(history_root);
+        }
+        HistorySource::Archive {
+            env,
+            file,
+            expected_root,
+        } => {
+            log::info!(?file, "Importing history archive");
+            return Ok(import_history(env, &file, &expected_root)?);
+        }
+        HistorySource::Build(env) => env,
+    };
 
     // Build history tree
     log::info!(
-        pow_block_number = final_block.number,
+        pow_block_number = cutting_pow_block_number,
         "Building history tree. This may take some time"
     );
     let start = Instant::now();
-    let history_root = match get_history_root(client, final_block.number, env).await {
+    match get_history_root(
+        client,
+        cutting_pow_block_number,
+        env,
+        pow_network_id,
+        None,
+        progress,
+    )
+    .await
+    {
         Ok(history_root) => {
             let duration = start.elapsed();
             log::info!(
@@ -53,71 +82,294 @@ pub async fn get_pos_genesis(
                 history_root = history_root.to_hex(),
                 "Finished building history tree"
             );
-            history_root
+            Ok(history_root)
         }
         Err(e) => {
             log::error!(error = ?e, "Failed to build history root");
-            std::process::exit(1);
+            Err(Error::History(e))
+        }
+    }
+}
+
+/// Gets the timestamp of the PoS genesis block according to the timestamp
+/// strategy of the profile
+///
+/// The timestamp must not be before the confirmation block. If the
+/// confirmation block is not mined yet, it must not be before the final block.
+async fn get_genesis_timestamp(
+    client: &Client,
+    profile: &GenesisProfile,
+    pow_reg_window: &PoWRegistrationWindow,
+    final_block: &Block,
+) -> Result<u64, Error> {
+    let confirmation_block_number = final_block.number + pow_reg_window.confirmations;
+    // The confirmation block is not mined yet only if it is past the head of
+    // the chain, any other failure to get it is an error.
+    let confirmation_block = if confirmation_block_number > client.block_number().await? {
+        None
+    } else {
+        Some(
+            client
+                .get_block_by_number(confirmation_block_number, false)
+                .await?,
+        )
+    };
+
+    let timestamp = match &profile.timestamp {
+        TimestampStrategy::Estimated => profile.estimated_genesis_timestamp(
+            final_block.timestamp as u64,
+            pow_reg_window.confirmations,
+        ),
+        TimestampStrategy::ConfirmationBlock => {
+            confirmation_block
+                .as_ref()
+                .ok_or(Error::ConfirmationBlockNotMined(confirmation_block_number))?
+                .timestamp as u64
         }
+        TimestampStrategy::Absolute { timestamp } => *timestamp,
     };
 
-    // The PoS genesis timestamp is the cutting block timestamp plus a custom delay
-    let pos_genesis_ts =
-        pow_reg_window.confirmations as u64 * POW_BLOCK_TIME_MS + final_block.timestamp as u64;
-    // The parent election hash of the PoS genesis is the hash of the PoW genesis block
-    let parent_election_hash = Blake2bHash::from_str(&pow_genesis.hash)?;
-    // The parent hash of the PoS genesis is the hash of cutting block
-    let parent_hash = Blake2bHash::from_str(&final_block.hash)?;
+    let confirmation_timestamp =
+        confirmation_block.as_ref().unwrap_or(final_block).timestamp as u64;
+    if timestamp < confirmation_timestamp {
+        log::error!(
+            timestamp,
+            confirmation_timestamp,
+            strategy = ?profile.timestamp,
+            "PoS genesis timestamp is before the confirmation block"
+        );
+        return Err(Error::TimestampBeforeConfirmation {
+            timestamp,
+            confirmation_timestamp,
+        });
+    }
+    Ok(timestamp)
+}
 
-    // Build up the VRF seed using a random seed that comes from the final block hash
-    let mut parent_hash_bytes = [0u8; 32];
-    parent_hash_bytes.copy_from_slice(parent_hash.as_slice());
-    let mut rng = StdRng::from_seed(parent_hash_bytes);
-    let vrf_seed = VrfSeed::default().sign_next_with_rng(&KeyPair::generate(&mut rng), &mut rng);
+/// Gets the final block stage: the final block of the registration window and
+/// the PoS genesis timestamp according to the profile
+pub async fn get_final_block_stage(
+    client: &Client,
+    pow_reg_window: &PoWRegistrationWindow,
+    profile: &GenesisProfile,
+) -> Result<FinalBlockStage, Error> {
+    // Get block according to arguments and check if it exists
+    let final_block = client
+        .get_block_by_hash(&pow_reg_window.final_block, false)
+        .await
+        .map_err(|_| {
+            log::error!(
+                hash = pow_reg_window.final_block,
+                "Could not find provided block"
+            );
+            Error::UnknownBlock
+        })?;
+    let pow_genesis = client.get_block_by_number(1, false).await?;
+    let genesis_timestamp =
+        get_genesis_timestamp(client, profile, pow_reg_window, &final_block).await?;
+
+    Ok(FinalBlockStage {
+        hash: final_block.hash,
+        number: final_block.number,
+        timestamp: final_block.timestamp as u64,
+        pow_genesis_hash: pow_genesis.hash,
+        genesis_timestamp,
+    })
+}
+
+/// Gets the accounts stage: the PoW accounts state at the final block
+pub async fn get_accounts_stage(
+    client: &Client,
+    final_block: &FinalBlockStage,
+    progress: &dyn ProgressSubscriber,
+) -> Result<AccountsStage, Error> {
+    let block = client
+        .get_block_by_hash(&final_block.hash, false)
+        .await
+        .map_err(|_| {
+            log::error!(hash = final_block.hash, "Could not find provided block");
+            Error::UnknownBlock
+        })?;
 
     log::info!("Getting PoW account state");
-    let genesis_accounts = get_accounts(client, &final_block, pos_genesis_ts).await?;
-
-    let (genesis_stakers, genesis_validators) =
-        if let Some(registered_agents) = pos_registered_agents {
-            (registered_agents.stakers, registered_agents.validators)
-        } else {
-            log::info!("Getting registered validators in the PoW chain");
-            let genesis_validators = get_validators(
-                client,
-                pow_reg_window.validator_start..pow_reg_window.pre_stake_start,
-            )
-            .await?;
+    let genesis_accounts =
+        get_accounts(client, &block, final_block.genesis_timestamp, progress).await?;
+    Ok(AccountsStage {
+        final_block_hash: final_block.hash.clone(),
+        final_block_number: final_block.number,
+        basic_accounts: genesis_accounts.basic_accounts,
+        vesting_accounts: genesis_accounts.vesting_accounts,
+        htlc_accounts: genesis_accounts.htlc_accounts,
+    })
+}
 
-            log::info!("Getting registered stakers in the PoW chain");
-            get_stakers(
-                client,
-                &genesis_validators,
-                pow_reg_window.pre_stake_start..pow_reg_window.pre_stake_end,
-            )
-            .await?
-        };
+/// Gets the agents stage: the validators and stakers registered in the PoW chain
+pub async fn get_agents_stage(
+    client: &Client,
+    final_block: &FinalBlockStage,
+    pow_reg_window: &PoWRegistrationWindow,
+) -> Result<AgentsStage, Error> {
+    log::info!("Getting registered validators in the PoW chain");
+    let genesis_validators = get_validators(
+        client,
+        pow_reg_window.validator_start..pow_reg_window.pre_stake_start,
+    )
+    .await?;
+
+    log::info!("Getting registered stakers in the PoW chain");
+    let (stakers, validators) = get_stakers(
+        client,
+        &genesis_validators,
+        pow_reg_window.pre_stake_start..pow_reg_window.pre_stake_end,
+    )
+    .await?;
+    Ok(AgentsStage::new(
+        PoSRegisteredAgents {
+            validators,
+            stakers,
+        },
+        final_block,
+    ))
+}
+
+/// Gets the history stage: the history root of the PoS genesis block
+pub async fn get_history_stage(
+    client: &Client,
+    final_block: &FinalBlockStage,
+    history: HistorySource,
+    profile: &GenesisProfile,
+    progress: &dyn ProgressSubscriber,
+) -> Result<HistoryStage, Error> {
+    let history_root = get_genesis_history_root(
+        client,
+        final_block.number,
+        history,
+        profile.network.pow_network_id(),
+        progress,
+    )
+    .await?;
+    Ok(HistoryStage {
+        final_block_hash: final_block.hash.clone(),
+        final_block_number: final_block.number,
+        history_root,
+    })
+}
+
+/// Assembles the genesis config from the saved stages without RPC access
+pub fn assemble_genesis(
+    profile: &GenesisProfile,
+    final_block: &FinalBlockStage,
+    accounts: AccountsStage,
+    agents: AgentsStage,
+    history: HistoryStage,
+) -> Result<GenesisConfig, Error> {
+    final_block.check_stage(
+        AccountsStage::FILE_NAME,
+        &accounts.final_block_hash,
+        accounts.final_block_number,
+    )?;
+    final_block.check_stage(
+        AgentsStage::FILE_NAME,
+        &agents.final_block_hash,
+        agents.final_block_number,
+    )?;
+    final_block.check_stage(
+        HistoryStage::FILE_NAME,
+        &history.final_block_hash,
+        history.final_block_number,
+    )?;
+
+    // The parent election hash of the PoS genesis is the hash of the PoW genesis block
+    let parent_election_hash = Blake2bHash::from_str(&final_block.pow_genesis_hash)?;
+    // The parent hash of the PoS genesis is the hash of cutting block
+    let parent_hash = Blake2bHash::from_str(&final_block.hash)?;
+
+    // Build up the VRF seed deterministically from the final block hash
+    let vrf_seed = derive_vrf_seed(profile.vrf_seed_version, &parent_hash)?;
 
     Ok(GenesisConfig {
-        seed_message: Some("Albatross TestNet".to_string()),
+        seed_message: Some(profile.seed_message.clone()),
         vrf_seed: Some(vrf_seed),
         parent_election_hash: Some(parent_election_hash),
         parent_hash: Some(parent_hash),
-        history_root: Some(history_root),
+        history_root: Some(history.history_root),
         block_number: final_block.number,
-        timestamp: Some(OffsetDateTime::from_unix_timestamp(pos_genesis_ts as i64)?),
-        validators: genesis_validators
-            .into_iter()
-            .map(|validator| validator.validator)
-            .collect(),
-        stakers: genesis_stakers,
-        basic_accounts: genesis_accounts.basic_accounts,
-        vesting_accounts: genesis_accounts.vesting_accounts,
-        htlc_accounts: genesis_accounts.htlc_accounts,
+        timestamp: Some(OffsetDateTime::from_unix_timestamp(
+            final_block.genesis_timestamp as i64,
+        )?),
+        validators: agents.validators,
+        stakers: agents.stakers,
+        basic_accounts: accounts.basic_accounts,
+        vesting_accounts: accounts.vesting_accounts,
+        htlc_accounts: accounts.htlc_accounts,
     })
 }
 
+/// Gets the genesis config file
+///
+/// The `profile` determines the networks, the seed message, the PoW timing
+/// parameters and the timestamp strategy of the genesis.
+/// The history root is obtained from `history`, which allows to skip replaying
+/// the PoW history when it is already known.
+/// The progress of the history and accounts migration is reported to `progress`.
+/// A statistics and sanity report of the generated genesis is logged.
+pub async fn get_pos_genesis(
+    client: &Client,
+    pow_reg_window: &PoWRegistrationWindow,
+    history: HistorySource,
+    profile: &GenesisProfile,
+    pos_registered_agents: Option<PoSRegisteredAgents>,
+    progress: &dyn ProgressSubscriber,
+) -> Result<GenesisConfig, Error> {
+    let final_block = get_final_block_stage(client, pow_reg_window, profile).await?;
+    let history = get_history_stage(client, &final_block, history, profile, progress).await?;
+    let accounts = get_accounts_stage(client, &final_block, progress).await?;
+    let agents = match pos_registered_agents {
+        Some(registered_agents) => AgentsStage::new(registered_agents, &final_block),
+        None => get_agents_stage(client, &final_block, pow_reg_window).await?,
+    };
+
+    let genesis_config = assemble_genesis(profile, &final_block, accounts, agents, history)?;
+    GenesisReport::new(&genesis_config, profile.max_validator_stake_percent).log();
+    Ok(genesis_config)
+}
+
+/// Gets the stake of each validator registered in the genesis config, which
+/// is its deposit plus the stake delegated to it
+pub(crate) fn validator_stakes(genesis_config: &GenesisConfig) -> HashMap<Address, Coin> {
+    let mut stakes: HashMap<Address, Coin> = genesis_config
+        .validators
+        .iter()
+        .map(|validator| {
+            (
+                validator.validator_address.clone(),
+                Coin::from_u64_unchecked(VALIDATOR_DEPOSIT),
+            )
+        })
+        .collect();
+    for staker in &genesis_config.stakers {
+        if let Some(stake) = stakes.get_mut(&staker.delegation) {
+            *stake += staker.balance;
+        }
+    }
+    stakes
+}
+
 /// Write the genesis config file to a TOML file
 pub fn write_pos_genesis(file_path: &str, genesis_config: GenesisConfig) -> Result<(), Error> {
     Ok(fs::write(file_path, toml::to_string(&genesis_config)?)?)
 }
+
+/// Builds the PoS genesis block of a genesis config along with its accounts
+pub fn build_genesis_block(genesis_config: &GenesisConfig) -> Result<GenesisInfo, Error> {
+    let env = VolatileDatabase::new(DEFAULT_DB_MAX_TABLES).map_err(|error| {
+        log::error!(?error, "Failed to create volatile database");
+        Error::Database
+    })?;
+    Ok(GenesisBuilder::from_config(genesis_config.clone())?.generate(env)?)
+}
+
+/// Read the genesis config file from a TOML file
+pub fn read_pos_genesis(file_path: &str) -> Result<GenesisConfig, Error> {
+    Ok(toml::from_str(&fs::read_to_string(file_path)?)?)
+}
@@ -1,18 +1,179 @@
-use std::{path::Path, time::Instant};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Instant,
+};
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use log::level_filters::LevelFilter;
-use nimiq_database::mdbx::MdbxDatabase;
+use nimiq_database::DatabaseProxy;
+use nimiq_genesis_builder::{config::GenesisConfig, GenesisInfo};
+use nimiq_hash::Blake2bHash;
+use nimiq_history_migration::database::{open_history_database, DatabaseSettings, NetworkPreset};
+use nimiq_keys::{KeyPair, PrivateKey};
+use nimiq_migration_progress::TerminalProgress;
 use nimiq_rpc::Client;
 use tracing_subscriber::{filter::Targets, layer::SubscriberExt, util::SubscriberInitExt, Layer};
 use url::Url;
 
-use nimiq_genesis_migration::{get_pos_genesis, types::PoWRegistrationWindow, write_pos_genesis};
+use nimiq_genesis_migration::{
+    assemble_genesis,
+    attestation::{verify_attestations, Attestation},
+    binary::{genesis_config_from_binary, read_binary_genesis, write_binary_genesis},
+    build_genesis_block,
+    diff::GenesisDiff,
+    get_accounts_stage, get_agents_stage, get_final_block_stage, get_history_stage,
+    get_pos_genesis,
+    manifest::{file_sha256, GenesisManifest, TOOL_VERSION},
+    profile::{GenesisProfile, TimestampStrategy, MAX_VALIDATOR_STAKE_PERCENT},
+    read_pos_genesis,
+    report::GenesisReport,
+    stages::{AccountsStage, AgentsStage, FinalBlockStage, HistoryStage, Stage},
+    types::{HistorySource, PoWRegistrationWindow},
+    vrf::{derive_vrf_seed, VRF_SEED_VERSION},
+    write_pos_genesis,
+};
 
 /// Command line arguments for the binary
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Operations supported by the binary
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Builds the PoS genesis from the PoW chain
+    Build(BuildArgs),
+    /// Rebuilds the PoS genesis from the inputs recorded in a genesis manifest
+    /// and checks that the result matches the manifest
+    RebuildFromManifest {
+        /// RPC connection URL to use
+        #[arg(short, long)]
+        rpc: String,
+
+        /// Genesis manifest file to rebuild from
+        #[arg(short, long)]
+        manifest: String,
+
+        /// TOML output file name
+        #[arg(short, long)]
+        file: String,
+
+        #[command(subcommand)]
+        history: ManifestHistoryCommand,
+    },
+    /// Verifies a genesis config field by field against one rebuilt from the
+    /// PoW chain using a genesis manifest, or against a recorded snapshot
+    Verify {
+        /// Genesis config TOML file to verify
+        #[arg(short, long)]
+        genesis: String,
+
+        #[command(subcommand)]
+        expected: VerifyCommand,
+    },
+    /// Prints the VRF seed derived from a final PoW block hash
+    VrfSeed {
+        /// Hash of the final PoW block
+        #[arg(short, long)]
+        final_hash: String,
+
+        /// Version of the VRF seed derivation
+        #[arg(short, long, default_value_t = VRF_SEED_VERSION)]
+        version: u8,
+    },
+    /// Fetches a stage of the genesis generation from the PoW chain and saves
+    /// it to a stage directory
+    Stage {
+        #[command(subcommand)]
+        stage: StageCommand,
+    },
+    /// Assembles the PoS genesis from the stages saved in a stage directory,
+    /// without RPC access
+    Assemble {
+        /// Stage directory
+        #[arg(long)]
+        dir: String,
+
+        /// TOML output file name
+        #[arg(short, long)]
+        file: String,
+
+        /// Optional binary output file name
+        #[arg(long)]
+        binary: Option<String>,
+    },
+    /// Attests a genesis by signing its hash, and optionally its manifest, with
+    /// the signing key of a registered validator
+    Attest {
+        /// Genesis config TOML file to attest
+        #[arg(short, long)]
+        genesis: String,
+
+        /// Optional genesis manifest to attest. It must describe the genesis
+        /// being attested.
+        #[arg(short, long)]
+        manifest: Option<String>,
+
+        /// File with the hex encoded Schnorr private signing key
+        #[arg(short, long)]
+        key_file: String,
+
+        /// Attestation output file name
+        #[arg(short, long)]
+        output: String,
+    },
+    /// Verifies validator attestations and reports the stake agreeing on each
+    /// genesis hash
+    VerifyAttestations {
+        /// Genesis config TOML file with the registered validators
+        #[arg(short, long)]
+        genesis: String,
+
+        /// Attestation files
+        #[arg(required = true)]
+        attestations: Vec<String>,
+    },
+    /// Logs the statistics and sanity report of a genesis config
+    Report {
+        /// Genesis config TOML file to report on
+        #[arg(short, long)]
+        genesis: String,
+
+        /// Share of the total stake in percent above which a validator is
+        /// flagged
+        #[arg(long, default_value_t = MAX_VALIDATOR_STAKE_PERCENT)]
+        max_validator_stake_percent: u8,
+    },
+    /// Converts a genesis config TOML file into a binary genesis file
+    TomlToBinary {
+        /// Genesis config TOML input file
+        #[arg(short, long)]
+        toml: String,
+
+        /// Binary genesis output file
+        #[arg(short, long)]
+        binary: String,
+    },
+    /// Converts a binary genesis file into a genesis config TOML file
+    BinaryToToml {
+        /// Binary genesis input file
+        #[arg(short, long)]
+        binary: String,
+
+        /// Genesis config TOML output file
+        #[arg(short, long)]
+        toml: String,
+    },
+}
+
+/// Arguments for building the PoS genesis
+#[derive(clap::Args, Debug)]
+struct BuildArgs {
     /// RPC connection URL to use
     #[arg(short, long)]
     rpc: String,
@@ -21,33 +182,229 @@ struct Args {
     #[arg(short, long)]
     file: String,
 
-    /// Block number of the block that starts the validator registration window
-    #[arg(short, long)]
-    validator_start: u32,
+    /// Optional binary output file name
+    #[arg(long)]
+    binary: Option<String>,
 
-    /// Block number of the block that starts the pre-stake registration window
+    /// Hash of the block will be taken as the genesis block for the PoS chain
     #[arg(short, long)]
-    prestake_start: u32,
+    final_hash: String,
 
-    /// Block number of the block that ends the pre-stake registration window
-    #[arg(short, long)]
-    prestake_end: u32,
+    #[command(flatten)]
+    profile_args: ProfileArgs,
 
-    /// Hash of the block will be taken as the genesis block for the PoS chain
+    #[command(subcommand)]
+    history: HistoryCommand,
+}
+
+/// Arguments selecting the genesis profile
+#[derive(clap::Args, Debug)]
+struct ProfileArgs {
+    /// Network to migrate. Its built-in genesis profile is used unless a custom
+    /// profile is given. There is no built-in MainNet profile yet.
+    #[arg(short, long, value_enum)]
+    network: NetworkPreset,
+
+    /// TOML file with a custom genesis profile for the network to use instead
+    /// of its built-in one
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// Block number of the block that starts the validator registration window.
+    /// Overrides the one of the genesis profile.
     #[arg(short, long)]
-    final_hash: String,
+    validator_start: Option<u32>,
 
-    /// Genesis delay in minutes
+    /// Block number of the block that ends the validator registration window.
+    /// Overrides the one of the genesis profile.
+    #[arg(long)]
+    validator_end: Option<u32>,
+
+    /// Block number of the block that starts the pre-stake registration window.
+    /// Overrides the one of the genesis profile.
     #[arg(short, long)]
-    confirmations: u32,
+    prestake_start: Option<u32>,
 
-    /// TOML output file name
+    /// Block number of the block that ends the pre-stake registration window.
+    /// Overrides the one of the genesis profile.
     #[arg(short, long)]
-    db_path: String,
+    prestake_end: Option<u32>,
 
-    /// Set to true for testnet usage
+    /// Genesis delay in number of PoW blocks. Overrides the one of the genesis
+    /// profile.
     #[arg(short, long)]
-    testnet: bool,
+    confirmations: Option<u32>,
+
+    /// Use the timestamp of the confirmation block, which must already be
+    /// mined, as the genesis timestamp. Overrides the timestamp strategy of the
+    /// genesis profile.
+    #[arg(long)]
+    confirmation_timestamp: bool,
+
+    /// Agreed unix timestamp to use as the genesis timestamp. Overrides the
+    /// timestamp strategy of the genesis profile.
+    #[arg(long, conflicts_with = "confirmation_timestamp")]
+    absolute_timestamp: Option<u64>,
+
+    /// Version of the VRF seed derivation. Overrides the one of the genesis
+    /// profile.
+    #[arg(long)]
+    vrf_seed_version: Option<u8>,
+
+    /// Share of the total stake in percent above which a validator is flagged
+    /// in the genesis report. Overrides the one of the genesis profile.
+    #[arg(long)]
+    max_validator_stake_percent: Option<u8>,
+}
+
+/// Sources of the history root
+#[derive(Subcommand, Debug)]
+enum HistoryCommand {
+    /// Builds the history store from the PoW chain
+    FromChain {
+        /// Path to the history store database
+        #[arg(short, long)]
+        db_path: String,
+
+        #[command(flatten)]
+        db_settings: DatabaseSettings,
+    },
+    /// Uses a known history root instead of building the history store
+    Precomputed {
+        /// Known history root
+        #[arg(long, value_parser = Blake2bHash::from_str)]
+        history_root: Blake2bHash,
+    },
+    /// Imports a history archive instead of building the history store
+    Archive {
+        /// History archive to import
+        #[arg(long)]
+        file: String,
+
+        /// Known history root, which the history root of the archive must match
+        #[arg(long, value_parser = Blake2bHash::from_str)]
+        history_root: Blake2bHash,
+
+        /// Path to the history store database to import the archive into
+        #[arg(short, long)]
+        db_path: String,
+
+        #[command(flatten)]
+        db_settings: DatabaseSettings,
+    },
+}
+
+/// Sources of the history root when rebuilding a genesis from its manifest
+#[derive(Subcommand, Debug)]
+enum ManifestHistoryCommand {
+    /// Builds the history store from the PoW chain
+    FromChain {
+        /// Path to the history store database
+        #[arg(short, long)]
+        db_path: String,
+
+        #[command(flatten)]
+        db_settings: DatabaseSettings,
+    },
+    /// Imports a history archive, whose history root must match the one of the
+    /// manifest
+    Archive {
+        /// History archive to import
+        #[arg(long)]
+        file: String,
+
+        /// Path to the history store database to import the archive into
+        #[arg(short, long)]
+        db_path: String,
+
+        #[command(flatten)]
+        db_settings: DatabaseSettings,
+    },
+    /// Trusts the history root recorded in the manifest without rebuilding the
+    /// history
+    TrustManifest,
+}
+
+/// Genesis configs a genesis config can be verified against
+#[derive(Subcommand, Debug)]
+enum VerifyCommand {
+    /// Rebuilds the genesis from the PoW chain using the inputs of a genesis
+    /// manifest
+    Manifest {
+        /// RPC connection URL to use for rebuilding the genesis
+        #[arg(short, long)]
+        rpc: String,
+
+        /// Genesis manifest with the inputs to rebuild the genesis from
+        #[arg(short, long)]
+        manifest: String,
+
+        #[command(subcommand)]
+        history: ManifestHistoryCommand,
+    },
+    /// Compares against a recorded genesis config
+    Snapshot {
+        /// Recorded genesis config TOML file
+        #[arg(short, long)]
+        snapshot: String,
+    },
+}
+
+/// Stages of the genesis generation that are fetched from the PoW chain and
+/// saved to a stage directory
+#[derive(Subcommand, Debug)]
+enum StageCommand {
+    /// Saves the genesis profile and the final block. This stage must be saved
+    /// before the other ones.
+    FinalBlock {
+        /// RPC connection URL to use
+        #[arg(short, long)]
+        rpc: String,
+
+        /// Stage directory
+        #[arg(long)]
+        dir: String,
+
+        /// Hash of the block will be taken as the genesis block for the PoS chain
+        #[arg(short, long)]
+        final_hash: String,
+
+        #[command(flatten)]
+        profile_args: ProfileArgs,
+    },
+    /// Saves the PoW accounts state at the final block
+    Accounts {
+        /// RPC connection URL to use
+        #[arg(short, long)]
+        rpc: String,
+
+        /// Stage directory
+        #[arg(long)]
+        dir: String,
+    },
+    /// Saves the validators and stakers registered in the PoW chain
+    Agents {
+        /// RPC connection URL to use
+        #[arg(short, long)]
+        rpc: String,
+
+        /// Stage directory
+        #[arg(long)]
+        dir: String,
+    },
+    /// Saves the history root
+    History {
+        /// RPC connection URL to use
+        #[arg(short, long)]
+        rpc: String,
+
+        /// Stage directory
+        #[arg(long)]
+        dir: String,
+
+        #[command(subcommand)]
+        history: HistoryCommand,
+    },
 }
 
 fn initialize_logging() {
@@ -64,63 +421,748 @@ fn initialize_logging() {
         .init();
 }
 
-#[tokio::main]
-async fn main() {
-    initialize_logging();
-
-    let args = Args::parse();
-    let url = match Url::parse(&args.rpc) {
-        Ok(url) => url,
+/// Connects to the PoW chain
+fn connect(rpc: &str) -> Client {
+    match Url::parse(rpc) {
+        Ok(url) => Client::new(url),
         Err(error) => {
             log::error!(?error, "Invalid RPC URL");
             std::process::exit(1);
         }
+    }
+}
+
+/// Generates the PoS genesis from the PoW chain
+async fn build_genesis(
+    client: &Client,
+    profile: &GenesisProfile,
+    pow_registration_window: &PoWRegistrationWindow,
+    history: HistorySource,
+) -> GenesisConfig {
+    log::info!("Generating genesis configuration from PoW chain");
+    let start = Instant::now();
+    match get_pos_genesis(
+        client,
+        pow_registration_window,
+        history,
+        profile,
+        None,
+        &TerminalProgress::default(),
+    )
+    .await
+    {
+        Ok(config) => {
+            let duration = start.elapsed();
+            log::info!(
+                duration = humantime::format_duration(duration).to_string(),
+                "Finished generating genesis configuration"
+            );
+            config
+        }
+        Err(error) => {
+            log::error!(?error, "Failed to build PoS genesis");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Builds the PoS genesis block of a genesis config
+fn build_block(genesis_config: &GenesisConfig) -> GenesisInfo {
+    log::info!("Building PoS genesis block");
+    match build_genesis_block(genesis_config) {
+        Ok(genesis) => genesis,
+        Err(error) => {
+            log::error!(?error, "Failed to build PoS genesis block");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Writes the PoS genesis to `file` along with its manifest and returns the
+/// manifest. The PoS genesis is also written in binary form to `binary_file`
+/// if given.
+fn write_genesis(
+    file: &str,
+    binary_file: Option<&str>,
+    genesis_config: GenesisConfig,
+    profile: &GenesisProfile,
+    pow_registration_window: &PoWRegistrationWindow,
+) -> GenesisManifest {
+    log::info!(filename = file, "Writing PoS genesis to file");
+    if let Err(error) = write_pos_genesis(file, genesis_config.clone()) {
+        log::error!(?error, "Could not write genesis config file");
+        std::process::exit(1);
+    }
+
+    let genesis = build_block(&genesis_config);
+    if let Some(binary_file) = binary_file {
+        log::info!(filename = binary_file, "Writing binary PoS genesis to file");
+        if let Err(error) = write_binary_genesis(Path::new(binary_file), &genesis) {
+            log::error!(?error, "Could not write binary genesis file");
+            std::process::exit(1);
+        }
+    }
+
+    let manifest = match GenesisManifest::new(
+        Path::new(file),
+        &genesis_config,
+        &genesis,
+        profile,
+        pow_registration_window,
+    ) {
+        Ok(manifest) => manifest,
+        Err(error) => {
+            log::error!(?error, "Failed to build genesis manifest");
+            std::process::exit(1);
+        }
     };
-    let client = Client::new(url);
-    let pow_registration_window = PoWRegistrationWindow {
-        pre_stake_start: args.prestake_start,
-        pre_stake_end: args.prestake_end,
-        validator_start: args.validator_start,
-        final_block: args.final_hash,
-        confirmations: args.confirmations,
+    log::info!(
+        genesis_hash = manifest.digest.genesis_hash.to_hex(),
+        state_root = manifest.digest.state_root.to_hex(),
+        staking_items_digest = manifest.digest.staking_items_digest.to_hex(),
+        "Built PoS genesis block"
+    );
+
+    let manifest_file = GenesisManifest::path_for(Path::new(file));
+    if let Err(error) = manifest.write(&manifest_file) {
+        log::error!(?error, "Could not write genesis manifest file");
+        std::process::exit(1);
+    }
+    log::info!(filename = ?manifest_file, "Finished writing genesis manifest");
+    manifest
+}
+
+/// Reads a genesis manifest, warning if it was generated by another version of
+/// the tool
+fn read_manifest(file: &str) -> GenesisManifest {
+    let manifest = match GenesisManifest::read(Path::new(file)) {
+        Ok(manifest) => manifest,
+        Err(error) => {
+            log::error!(?error, file, "Could not read genesis manifest");
+            std::process::exit(1);
+        }
     };
+    if manifest.inputs.profile.vrf_seed_version != VRF_SEED_VERSION {
+        log::warn!(
+            manifest_version = manifest.inputs.profile.vrf_seed_version,
+            current_version = VRF_SEED_VERSION,
+            "The manifest uses a different VRF seed derivation version"
+        );
+    }
+    if manifest.inputs.tool_version != TOOL_VERSION {
+        log::warn!(
+            manifest_version = manifest.inputs.tool_version,
+            tool_version = TOOL_VERSION,
+            "The manifest was generated by a different version of the tool"
+        );
+    }
+    manifest
+}
 
-    // Create DB environment
-    let network_id = if args.testnet { "test" } else { "main" };
-    let db_name = format!("{network_id}-history-consensus").to_lowercase();
-    let db_path = Path::new(&args.db_path).join(db_name);
-    let env = match MdbxDatabase::new_with_max_readers(
-        db_path.clone(),
-        100 * 1024 * 1024 * 1024,
-        20,
-        600,
-    ) {
-        Ok(db) => db,
+/// Gets the source of the history root selected by the arguments for
+/// rebuilding the PoS genesis of a manifest
+fn manifest_history_source(
+    history: ManifestHistoryCommand,
+    manifest: &GenesisManifest,
+) -> HistorySource {
+    let network = manifest.inputs.profile.network;
+    match history {
+        ManifestHistoryCommand::FromChain {
+            db_path,
+            db_settings,
+        } => HistorySource::Build(open_history_env(&db_path, network, &db_settings)),
+        ManifestHistoryCommand::Archive {
+            file,
+            db_path,
+            db_settings,
+        } => HistorySource::Archive {
+            env: open_history_env(&db_path, network, &db_settings),
+            file: PathBuf::from(file),
+            expected_root: manifest.inputs.history_root.clone(),
+        },
+        ManifestHistoryCommand::TrustManifest => {
+            log::warn!(
+                history_root = manifest.inputs.history_root.to_hex(),
+                "Using the history root of the manifest without rebuilding the history"
+            );
+            HistorySource::Precomputed(manifest.inputs.history_root.clone())
+        }
+    }
+}
+
+/// Rebuilds the PoS genesis from the inputs of a manifest, taking the history
+/// root from `history`
+async fn rebuild(
+    rpc: &str,
+    manifest: &GenesisManifest,
+    history: HistorySource,
+) -> (GenesisConfig, PoWRegistrationWindow) {
+    let client = connect(rpc);
+    let profile = &manifest.inputs.profile;
+    if let Err(error) = profile.validate() {
+        log::error!(?error, "Invalid block windows in genesis manifest");
+        std::process::exit(1);
+    }
+    let pow_registration_window =
+        profile.registration_window(manifest.inputs.final_block_hash.clone());
+
+    let genesis_config = build_genesis(&client, profile, &pow_registration_window, history).await;
+    (genesis_config, pow_registration_window)
+}
+
+/// Gets the genesis profile selected by the arguments
+fn resolve_profile(args: &ProfileArgs) -> GenesisProfile {
+    let mut profile = match &args.profile {
+        Some(file) => match GenesisProfile::from_file(Path::new(file)) {
+            Ok(profile) if profile.network == args.network => profile,
+            Ok(profile) => {
+                log::error!(
+                    file,
+                    expected = ?args.network,
+                    found = ?profile.network,
+                    "Genesis profile is for a different network"
+                );
+                std::process::exit(1);
+            }
+            Err(error) => {
+                log::error!(?error, file, "Invalid genesis profile");
+                std::process::exit(1);
+            }
+        },
+        None => match GenesisProfile::for_network(args.network) {
+            Some(profile) => profile,
+            None => {
+                log::error!(
+                    network = ?args.network,
+                    "There is no built-in genesis profile for this network, use a custom profile"
+                );
+                std::process::exit(1);
+            }
+        },
+    };
+    let block_windows = &mut profile.block_windows;
+    if let Some(validator_start) = args.validator_start {
+        block_windows.registration_start = validator_start;
+    }
+    if let Some(validator_end) = args.validator_end {
+        block_windows.registration_end = validator_end;
+    }
+    if let Some(prestake_start) = args.prestake_start {
+        block_windows.pre_stake_start = prestake_start;
+    }
+    if let Some(prestake_end) = args.prestake_end {
+        block_windows.pre_stake_end = prestake_end;
+    }
+    if let Some(confirmations) = args.confirmations {
+        block_windows.block_confirmations = confirmations;
+    }
+    if args.confirmation_timestamp {
+        profile.timestamp = TimestampStrategy::ConfirmationBlock;
+    }
+    if let Some(timestamp) = args.absolute_timestamp {
+        profile.timestamp = TimestampStrategy::Absolute { timestamp };
+    }
+    if let Some(vrf_seed_version) = args.vrf_seed_version {
+        profile.vrf_seed_version = vrf_seed_version;
+    }
+    if let Some(max_validator_stake_percent) = args.max_validator_stake_percent {
+        profile.max_validator_stake_percent = max_validator_stake_percent;
+    }
+    if let Err(error) = profile.validate() {
+        log::error!(?error, "Invalid block windows");
+        std::process::exit(1);
+    }
+    profile
+}
+
+/// Opens the history store database of a network
+fn open_history_env(
+    db_path: &str,
+    network: NetworkPreset,
+    db_settings: &DatabaseSettings,
+) -> DatabaseProxy {
+    match open_history_database(Path::new(db_path), network, db_settings) {
+        Ok((_, env)) => env,
         Err(e) => {
             log::error!(error = ?e, "Failed to create database");
             std::process::exit(1);
         }
+    }
+}
+
+/// Gets the source of the history root selected by the arguments
+fn history_source(history: HistoryCommand, network: NetworkPreset) -> HistorySource {
+    match history {
+        HistoryCommand::FromChain {
+            db_path,
+            db_settings,
+        } => HistorySource::Build(open_history_env(&db_path, network, &db_settings)),
+        HistoryCommand::Precomputed { history_root } => HistorySource::Precomputed(history_root),
+        HistoryCommand::Archive {
+            file,
+            history_root,
+            db_path,
+            db_settings,
+        } => HistorySource::Archive {
+            env: open_history_env(&db_path, network, &db_settings),
+            file: PathBuf::from(file),
+            expected_root: history_root,
+        },
+    }
+}
+
+async fn build(args: BuildArgs) {
+    let client = connect(&args.rpc);
+    let profile = resolve_profile(&args.profile_args);
+    let pow_registration_window = profile.registration_window(args.final_hash);
+    let history = history_source(args.history, profile.network);
+
+    let genesis_config = build_genesis(&client, &profile, &pow_registration_window, history).await;
+    write_genesis(
+        &args.file,
+        args.binary.as_deref(),
+        genesis_config,
+        &profile,
+        &pow_registration_window,
+    );
+}
+
+async fn rebuild_from_manifest(
+    rpc: String,
+    manifest_file: String,
+    file: String,
+    history: ManifestHistoryCommand,
+) {
+    let expected = read_manifest(&manifest_file);
+    let history = manifest_history_source(history, &expected);
+    let (genesis_config, pow_registration_window) = rebuild(&rpc, &expected, history).await;
+    let rebuilt = write_genesis(
+        &file,
+        None,
+        genesis_config,
+        &expected.inputs.profile,
+        &pow_registration_window,
+    );
+
+    let mut matches = true;
+    if rebuilt.inputs.final_block_number != expected.inputs.final_block_number {
+        log::error!(
+            expected = expected.inputs.final_block_number,
+            found = rebuilt.inputs.final_block_number,
+            "Final block number mismatch"
+        );
+        matches = false;
+    }
+    if rebuilt.inputs.history_root != expected.inputs.history_root {
+        log::error!(
+            expected = expected.inputs.history_root.to_hex(),
+            found = rebuilt.inputs.history_root.to_hex(),
+            "History root mismatch"
+        );
+        matches = false;
+    }
+    if rebuilt.config_sha256 != expected.config_sha256 {
+        log::error!(
+            expected = expected.config_sha256,
+            found = rebuilt.config_sha256,
+            "Genesis config SHA-256 mismatch"
+        );
+        matches = false;
+    }
+    if rebuilt.digest != expected.digest {
+        log::error!(
+            expected = ?expected.digest,
+            found = ?rebuilt.digest,
+            "Genesis block digest mismatch"
+        );
+        matches = false;
+    }
+    if !matches {
+        std::process::exit(1);
+    }
+    log::info!(
+        genesis_hash = rebuilt.digest.genesis_hash.to_hex(),
+        "Rebuilt genesis matches the manifest"
+    );
+}
+
+async fn verify(genesis_file: String, expected: VerifyCommand) {
+    let genesis_config = match read_pos_genesis(&genesis_file) {
+        Ok(genesis_config) => genesis_config,
+        Err(error) => {
+            log::error!(?error, file = genesis_file, "Could not read genesis config");
+            std::process::exit(1);
+        }
     };
 
-    log::info!("Generating genesis configuration from PoW chain");
-    let start = Instant::now();
-    let genesis_config = match get_pos_genesis(&client, &pow_registration_window, env, None).await {
-        Ok(config) => config,
+    let expected = match expected {
+        VerifyCommand::Manifest {
+            rpc,
+            manifest,
+            history,
+        } => {
+            let manifest = read_manifest(&manifest);
+            let history = manifest_history_source(history, &manifest);
+            rebuild(&rpc, &manifest, history).await.0
+        }
+        VerifyCommand::Snapshot { snapshot } => match read_pos_genesis(&snapshot) {
+            Ok(expected) => expected,
+            Err(error) => {
+                log::error!(?error, file = snapshot, "Could not read genesis snapshot");
+                std::process::exit(1);
+            }
+        },
+    };
+
+    let diff = GenesisDiff::compare(&expected, &genesis_config);
+    for difference in &diff.differences {
+        log::warn!(
+            field = difference.field,
+            expected = difference.expected.as_deref().unwrap_or("<missing>"),
+            found = difference.found.as_deref().unwrap_or("<missing>"),
+            "Genesis config difference"
+        );
+    }
+    if !diff.is_empty() {
+        log::error!(
+            differences = diff.differences.len(),
+            file = genesis_file,
+            "Genesis config does not match"
+        );
+        std::process::exit(1);
+    }
+    log::info!(file = genesis_file, "Genesis config matches");
+}
+
+fn vrf_seed(final_hash: String, version: u8) {
+    let final_hash = match Blake2bHash::from_str(&final_hash) {
+        Ok(hash) => hash,
         Err(error) => {
-            log::error!(?error, "Failed to build PoS genesis");
+            log::error!(?error, "Invalid final block hash");
             std::process::exit(1);
         }
     };
+    match derive_vrf_seed(version, &final_hash) {
+        Ok(vrf_seed) => match serde_json::to_string(&vrf_seed) {
+            Ok(vrf_seed) => log::info!(
+                version,
+                vrf_seed = vrf_seed.trim_matches('"'),
+                "Derived VRF seed"
+            ),
+            Err(error) => {
+                log::error!(?error, "Could not serialize VRF seed");
+                std::process::exit(1);
+            }
+        },
+        Err(error) => {
+            log::error!(?error, "Failed to derive VRF seed");
+            std::process::exit(1);
+        }
+    }
+}
 
-    log::info!(filename = args.file, "Writing PoS genesis to file");
-    if let Err(error) = write_pos_genesis(&args.file, genesis_config) {
+/// Reads a saved genesis stage
+fn read_stage<T: Stage>(dir: &str) -> T {
+    match T::read(Path::new(dir)) {
+        Ok(stage) => stage,
+        Err(error) => {
+            log::error!(
+                ?error,
+                dir,
+                file = T::FILE_NAME,
+                "Could not read genesis stage"
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Saves a genesis stage
+fn write_stage<T: Stage>(dir: &str, stage: &T) {
+    if let Err(error) = stage.write(Path::new(dir)) {
+        log::error!(
+            ?error,
+            dir,
+            file = T::FILE_NAME,
+            "Could not write genesis stage"
+        );
+        std::process::exit(1);
+    }
+    log::info!(dir, file = T::FILE_NAME, "Saved genesis stage");
+}
+
+async fn stage(command: StageCommand) {
+    match command {
+        StageCommand::FinalBlock {
+            rpc,
+            dir,
+            final_hash,
+            profile_args,
+        } => {
+            let client = connect(&rpc);
+            let profile = resolve_profile(&profile_args);
+            let pow_registration_window = profile.registration_window(final_hash);
+            match get_final_block_stage(&client, &pow_registration_window, &profile).await {
+                Ok(final_block) => {
+                    write_stage(&dir, &profile);
+                    write_stage(&dir, &final_block);
+                }
+                Err(error) => {
+                    log::error!(?error, "Failed to get the final block");
+                    std::process::exit(1);
+                }
+            }
+        }
+        StageCommand::Accounts { rpc, dir } => {
+            let client = connect(&rpc);
+            let final_block: FinalBlockStage = read_stage(&dir);
+            match get_accounts_stage(&client, &final_block, &TerminalProgress::default()).await {
+                Ok(accounts) => write_stage(&dir, &accounts),
+                Err(error) => {
+                    log::error!(?error, "Failed to get the PoW accounts");
+                    std::process::exit(1);
+                }
+            }
+        }
+        StageCommand::Agents { rpc, dir } => {
+            let client = connect(&rpc);
+            let profile: GenesisProfile = read_stage(&dir);
+            let final_block: FinalBlockStage = read_stage(&dir);
+            let pow_registration_window = profile.registration_window(final_block.hash.clone());
+            match get_agents_stage(&client, &final_block, &pow_registration_window).await {
+                Ok(agents) => write_stage(&dir, &agents),
+                Err(error) => {
+                    log::error!(?error, "Failed to get the registered agents");
+                    std::process::exit(1);
+                }
+            }
+        }
+        StageCommand::History { rpc, dir, history } => {
+            let client = connect(&rpc);
+            let profile: GenesisProfile = read_stage(&dir);
+            let final_block: FinalBlockStage = read_stage(&dir);
+            let history = history_source(history, profile.network);
+            match get_history_stage(
+                &client,
+                &final_block,
+                history,
+                &profile,
+                &TerminalProgress::default(),
+            )
+            .await
+            {
+                Ok(history) => write_stage(&dir, &history),
+                Err(error) => {
+                    log::error!(?error, "Failed to get the history root");
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+}
+
+fn assemble(dir: String, file: String, binary: Option<String>) {
+    let profile: GenesisProfile = read_stage(&dir);
+    let final_block: FinalBlockStage = read_stage(&dir);
+    let accounts: AccountsStage = read_stage(&dir);
+    let agents: AgentsStage = read_stage(&dir);
+    let history: HistoryStage = read_stage(&dir);
+
+    log::info!(dir, "Assembling genesis configuration from saved stages");
+    let genesis_config = match assemble_genesis(&profile, &final_block, accounts, agents, history) {
+        Ok(genesis_config) => genesis_config,
+        Err(error) => {
+            log::error!(?error, "Failed to assemble PoS genesis");
+            std::process::exit(1);
+        }
+    };
+    GenesisReport::new(&genesis_config, profile.max_validator_stake_percent).log();
+    let pow_registration_window = profile.registration_window(final_block.hash);
+    write_genesis(
+        &file,
+        binary.as_deref(),
+        genesis_config,
+        &profile,
+        &pow_registration_window,
+    );
+}
+
+/// Reads a genesis config
+fn read_genesis(file: &str) -> GenesisConfig {
+    match read_pos_genesis(file) {
+        Ok(genesis_config) => genesis_config,
+        Err(error) => {
+            log::error!(?error, file, "Could not read genesis config");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn attest(genesis_file: String, manifest: Option<String>, key_file: String, output: String) {
+    let genesis_config = read_genesis(&genesis_file);
+    let key_pair = match fs::read_to_string(&key_file)
+        .map_err(|error| error.to_string())
+        .and_then(|key| hex::decode(key.trim()).map_err(|error| error.to_string()))
+        .and_then(|key| PrivateKey::from_bytes(&key).map_err(|error| error.to_string()))
+    {
+        Ok(private_key) => KeyPair::from(private_key),
+        Err(error) => {
+            log::error!(error, file = key_file, "Invalid signing key file");
+            std::process::exit(1);
+        }
+    };
+
+    let genesis = build_block(&genesis_config);
+    // Only attest a manifest that describes the genesis being attested
+    let manifest_sha256 = manifest.map(|manifest| {
+        let manifest_genesis_hash = read_manifest(&manifest).digest.genesis_hash;
+        if manifest_genesis_hash != genesis.hash {
+            log::error!(
+                file = manifest,
+                manifest_genesis_hash = manifest_genesis_hash.to_hex(),
+                genesis_hash = genesis.hash.to_hex(),
+                "Genesis manifest describes a different genesis"
+            );
+            std::process::exit(1);
+        }
+        file_sha256(Path::new(&manifest)).unwrap_or_else(|error| {
+            log::error!(?error, file = manifest, "Could not read genesis manifest");
+            std::process::exit(1);
+        })
+    });
+    let attestation =
+        match Attestation::sign(&genesis_config, &key_pair, genesis.hash, manifest_sha256) {
+            Ok(attestation) => attestation,
+            Err(error) => {
+                log::error!(?error, "Could not attest genesis");
+                std::process::exit(1);
+            }
+        };
+    if let Err(error) = attestation.write(Path::new(&output)) {
+        log::error!(?error, "Could not write attestation file");
+        std::process::exit(1);
+    }
+    log::info!(
+        validator_address = attestation.validator_address,
+        genesis_hash = attestation.genesis_hash.to_hex(),
+        filename = output,
+        "Finished writing attestation"
+    );
+}
+
+fn verify_attestations_files(genesis_file: String, attestations: Vec<String>) {
+    let genesis_config = read_genesis(&genesis_file);
+    let files: Vec<PathBuf> = attestations.into_iter().map(PathBuf::from).collect();
+    let report = verify_attestations(&genesis_config, &files);
+
+    for (file, reason) in &report.rejected {
+        log::warn!(?file, reason, "Rejected attestation");
+    }
+    let total_stake = u64::from(report.total_stake);
+    for (genesis_hash, agreeing) in &report.by_hash {
+        let stake = u64::from(agreeing.stake);
+        let share_percent = if total_stake > 0 {
+            stake as f64 * 100.0 / total_stake as f64
+        } else {
+            0.0
+        };
+        log::info!(
+            genesis_hash = genesis_hash.as_str(),
+            validators = agreeing.validators.len(),
+            stake = %agreeing.stake,
+            share_percent = format!("{share_percent:.2}"),
+            total_stake = %report.total_stake,
+            "Stake agreeing on genesis hash"
+        );
+    }
+}
+
+fn report(genesis_file: String, max_validator_stake_percent: u8) {
+    let genesis_config = read_genesis(&genesis_file);
+    GenesisReport::new(&genesis_config, max_validator_stake_percent).log();
+}
+
+fn toml_to_binary(toml_file: String, binary_file: String) {
+    let genesis_config = match read_pos_genesis(&toml_file) {
+        Ok(genesis_config) => genesis_config,
+        Err(error) => {
+            log::error!(?error, file = toml_file, "Could not read genesis config");
+            std::process::exit(1);
+        }
+    };
+    let genesis = build_block(&genesis_config);
+    if let Err(error) = write_binary_genesis(Path::new(&binary_file), &genesis) {
+        log::error!(?error, "Could not write binary genesis file");
+        std::process::exit(1);
+    }
+    log::info!(
+        genesis_hash = genesis.hash.to_hex(),
+        filename = binary_file,
+        "Finished writing binary genesis"
+    );
+}
+
+fn binary_to_toml(binary_file: String, toml_file: String) {
+    let genesis = match read_binary_genesis(Path::new(&binary_file)) {
+        Ok(genesis) => genesis,
+        Err(error) => {
+            log::error!(?error, file = binary_file, "Could not read binary genesis");
+            std::process::exit(1);
+        }
+    };
+    log::info!("Rebuilding genesis config from binary genesis");
+    let genesis_config = match genesis_config_from_binary(&genesis) {
+        Ok(genesis_config) => genesis_config,
+        Err(error) => {
+            log::error!(?error, "Could not rebuild genesis config");
+            std::process::exit(1);
+        }
+    };
+    if let Err(error) = write_pos_genesis(&toml_file, genesis_config) {
         log::error!(?error, "Could not write genesis config file");
         std::process::exit(1);
     }
-    let duration = start.elapsed();
     log::info!(
-        duration = humantime::format_duration(duration).to_string(),
-        filename = args.file,
-        "Finished writing PoS genesis to file"
+        genesis_hash = genesis.hash.to_hex(),
+        filename = toml_file,
+        "Finished writing genesis config"
     );
 }
+
+#[tokio::main]
+async fn main() {
+    initialize_logging();
+
+    match Args::parse().command {
+        Command::Build(args) => build(args).await,
+        Command::RebuildFromManifest {
+            rpc,
+            manifest,
+            file,
+            history,
+        } => rebuild_from_manifest(rpc, manifest, file, history).await,
+        Command::Verify { genesis, expected } => verify(genesis, expected).await,
+        Command::VrfSeed {
+            final_hash,
+            version,
+        } => vrf_seed(final_hash, version),
+        Command::Stage { stage: command } => stage(command).await,
+        Command::Assemble { dir, file, binary } => assemble(dir, file, binary),
+        Command::Attest {
+            genesis,
+            manifest,
+            key_file,
+            output,
+        } => attest(genesis, manifest, key_file, output),
+        Command::VerifyAttestations {
+            genesis,
+            attestations,
+        } => verify_attestations_files(genesis, attestations),
+        Command::Report {
+            genesis,
+            max_validator_stake_percent,
+        } => report(genesis, max_validator_stake_percent),
+        Command::TomlToBinary { toml, binary } => toml_to_binary(toml, binary),
+        Command::BinaryToToml { binary, toml } => binary_to_toml(binary, toml),
+    }
+}
@@ -0,0 +1,139 @@
+//! Genesis manifest
+//!
+//! The manifest is a TOML sidecar file written next to the genesis config that
+//! records every input of the genesis generation and summarizes the resulting
+//! PoS genesis block. Operators can compare manifests to check whether they
+//! produced the same genesis, and the genesis can be rebuilt from its inputs.
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use nimiq_genesis_builder::{config::GenesisConfig, GenesisInfo};
+use nimiq_hash::{Blake2bHash, Blake2bHasher, Hasher};
+use nimiq_primitives::{key_nibbles::KeyNibbles, policy::Policy};
+
+use crate::{
+    profile::GenesisProfile,
+    types::{Error, PoWRegistrationWindow},
+};
+
+/// Version of the genesis tool
+pub const TOOL_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Gets the hex encoded SHA-256 of a file
+pub fn file_sha256(path: &Path) -> Result<String, Error> {
+    Ok(hex::encode(Sha256::digest(fs::read(path)?)))
+}
+
+/// Inputs that determine the generated PoS genesis
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct GenesisInputs {
+    /// Version of the tool that generated the genesis
+    pub tool_version: String,
+    /// Hash of the final block in the PoW chain
+    pub final_block_hash: String,
+    /// Block number of the final block in the PoW chain
+    pub final_block_number: u32,
+    /// History root of the PoS genesis block
+    pub history_root: Blake2bHash,
+    /// Genesis profile, including the effective registration window
+    /// boundaries and confirmations
+    pub profile: GenesisProfile,
+}
+
+/// Digest of the PoS genesis block resulting from a genesis config
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct GenesisDigest {
+    /// Hash of the PoS genesis block
+    pub genesis_hash: Blake2bHash,
+    /// Root of the accounts tree after applying the genesis accounts
+    pub state_root: Blake2bHash,
+    /// Blake2b digest of the serialized accounts tree items of the staking
+    /// contract. This is not the root of the staking contract subtree.
+    pub staking_items_digest: Blake2bHash,
+}
+
+impl GenesisDigest {
+    /// Computes the digest of a built PoS genesis block
+    pub fn new(genesis: &GenesisInfo) -> Result<Self, Error> {
+        // The staking contract is stored in the accounts tree under the
+        // staking contract address, so its items are the ones prefixed by it.
+        let staking_prefix = KeyNibbles::from(&Policy::STAKING_CONTRACT_ADDRESS);
+        let mut staking_hasher = Blake2bHasher::default();
+        for item in genesis
+            .accounts
+            .iter()
+            .filter(|item| staking_prefix.is_prefix_of(&item.key))
+        {
+            staking_hasher.write_all(&nimiq_serde::Serialize::serialize_to_vec(item))?;
+        }
+
+        Ok(GenesisDigest {
+            genesis_hash: genesis.hash.clone(),
+            state_root: genesis.block.state_root().clone(),
+            staking_items_digest: staking_hasher.finish(),
+        })
+    }
+}
+
+/// Manifest describing a generated PoS genesis
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GenesisManifest {
+    /// Hex encoded SHA-256 of the genesis config TOML file
+    pub config_sha256: String,
+    /// Inputs of the genesis generation
+    pub inputs: GenesisInputs,
+    /// Digest of the resulting PoS genesis block
+    pub digest: GenesisDigest,
+}
+
+impl GenesisManifest {
+    /// Builds the manifest of the genesis config written to `genesis_file`,
+    /// whose PoS genesis block is `genesis`
+    pub fn new(
+        genesis_file: &Path,
+        genesis_config: &GenesisConfig,
+        genesis: &GenesisInfo,
+        profile: &GenesisProfile,
+        pow_reg_window: &PoWRegistrationWindow,
+    ) -> Result<Self, Error> {
+        let history_root = genesis_config
+            .history_root
+            .clone()
+            .ok_or(Error::MissingHistoryRoot)?;
+        Ok(GenesisManifest {
+            config_sha256: file_sha256(genesis_file)?,
+            inputs: GenesisInputs {
+                tool_version: TOOL_VERSION.to_string(),
+                final_block_hash: pow_reg_window.final_block.clone(),
+                final_block_number: genesis_config.block_number,
+                history_root,
+                profile: profile.clone(),
+            },
+            digest: GenesisDigest::new(genesis)?,
+        })
+    }
+
+    /// Path of the manifest written next to the genesis config file
+    pub fn path_for(genesis_file: &Path) -> PathBuf {
+        let mut file_name = genesis_file.as_os_str().to_owned();
+        file_name.push(".manifest.toml");
+        PathBuf::from(file_name)
+    }
+
+    /// Reads a manifest from a TOML file
+    pub fn read(path: &Path) -> Result<Self, Error> {
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Writes the manifest to a TOML file
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        Ok(fs::write(path, toml::to_string(self)?)?)
+    }
+}
@@ -0,0 +1,206 @@
+//! Genesis profiles
+//!
+//! A genesis profile bundles the network dependent parameters of the genesis
+//! generation: the PoW and PoS networks, the seed message of the PoS genesis
+//! block, the block windows in the PoW chain and the PoW timing parameters.
+//! There are built-in profiles for the TestNet and DevNet presets and custom
+//! profiles can be loaded from a TOML file. There is no built-in MainNet profile
+//! until its block windows are decided.
+
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use nimiq_history_migration::database::NetworkPreset;
+
+use crate::{
+    types::{Error, PoWRegistrationWindow},
+    vrf::VRF_SEED_VERSION,
+};
+
+/// Default share of the total stake in percent above which a validator is
+/// flagged. A validator holding a third of the stake can halt the PoS chain.
+pub const MAX_VALIDATOR_STAKE_PERCENT: u8 = 33;
+
+/// PoW block registration window
+///
+/// The registration window is a set of blocks in the PoW chain that marks
+/// the start and end of different windows as follows:
+///
+///     1              2              3              4              5        6
+/// --- | ------------ | ------------ | ------------ | ------------ |------- |
+///
+/// 1. Validator registration window start block.
+/// 2. Validator registration window end block.
+/// 3. Pre-stake registration window start.
+/// 4. Pre-stake registration window end block. This block is also the activation
+///    window start.
+/// 5. The final block in the PoW chain that will be taken as genesis block for the
+///    PoS chain. This block must have a block number that can be an election block
+///    number in the PoS chain.
+/// 6. This is a block whose block number is a number of confirmations away from
+///    the final block described in 4.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BlockWindows {
+    /// Block number of the validator registration window start.
+    pub registration_start: u32,
+    /// Block number of the validator registration window end.
+    pub registration_end: u32,
+    /// Block number of the validator registration window end which is also
+    /// the pre stake registration window start.
+    pub pre_stake_start: u32,
+    /// Block number of the pre stake registration window end.
+    pub pre_stake_end: u32,
+    /// Number of confirmations after the final block needed for the PoS chain to
+    /// start.
+    pub block_confirmations: u32,
+}
+
+/// Strategy used to set the timestamp of the PoS genesis block
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "strategy", rename_all = "kebab-case")]
+pub enum TimestampStrategy {
+    /// Timestamp of the final block plus the confirmations multiplied by the
+    /// estimated PoW block time, in seconds
+    #[default]
+    Estimated,
+    /// Timestamp of the confirmation block, which must already be mined
+    ConfirmationBlock,
+    /// Agreed absolute timestamp
+    Absolute {
+        /// Unix timestamp of the PoS genesis block in seconds
+        timestamp: u64,
+    },
+}
+
+/// Network dependent parameters of the genesis generation
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct GenesisProfile {
+    /// Network being migrated. Determines the PoW network ID expected in the
+    /// migrated transactions and the resulting PoS network.
+    pub network: NetworkPreset,
+    /// Seed message of the PoS genesis block
+    pub seed_message: String,
+    /// Block windows in the PoW chain
+    pub block_windows: BlockWindows,
+    /// Estimated PoW block time in milliseconds
+    pub pow_block_time_ms: u64,
+    /// Strategy used to set the timestamp of the PoS genesis block
+    #[serde(default)]
+    pub timestamp: TimestampStrategy,
+    /// Version of the VRF seed derivation, see the `vrf` module
+    #[serde(default = "default_vrf_seed_version")]
+    pub vrf_seed_version: u8,
+    /// Share of the total stake in percent above which a validator is flagged
+    /// in the genesis report
+    #[serde(default = "default_max_validator_stake_percent")]
+    pub max_validator_stake_percent: u8,
+}
+
+fn default_vrf_seed_version() -> u8 {
+    VRF_SEED_VERSION
+}
+
+fn default_max_validator_stake_percent() -> u8 {
+    MAX_VALIDATOR_STAKE_PERCENT
+}
+
+impl GenesisProfile {
+    /// Gets the built-in profile of a network, if there is one
+    pub fn for_network(network: NetworkPreset) -> Option<Self> {
+        let profile = match network {
+            // The MainNet block windows are still to be decided
+            NetworkPreset::Main => return None,
+            NetworkPreset::Test => GenesisProfile {
+                network,
+                seed_message: "Albatross TestNet".to_string(),
+                block_windows: BlockWindows {
+                    registration_start: 2590000,
+                    registration_end: 2660000,
+                    pre_stake_start: 2660000,
+                    pre_stake_end: 2663100,
+                    block_confirmations: 10,
+                },
+                pow_block_time_ms: 60 * 1000,
+                timestamp: TimestampStrategy::Estimated,
+                vrf_seed_version: VRF_SEED_VERSION,
+                max_validator_stake_percent: MAX_VALIDATOR_STAKE_PERCENT,
+            },
+            // DevNet chains are short lived, so its windows start right after
+            // the PoW genesis block.
+            NetworkPreset::Dev => GenesisProfile {
+                network,
+                seed_message: "Albatross DevNet".to_string(),
+                block_windows: BlockWindows {
+                    registration_start: 1,
+                    registration_end: 100,
+                    pre_stake_start: 100,
+                    pre_stake_end: 200,
+                    block_confirmations: 5,
+                },
+                pow_block_time_ms: 60 * 1000,
+                timestamp: TimestampStrategy::Estimated,
+                vrf_seed_version: VRF_SEED_VERSION,
+                max_validator_stake_percent: MAX_VALIDATOR_STAKE_PERCENT,
+            },
+        };
+        Some(profile)
+    }
+
+    /// Loads a custom profile from a TOML file
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let profile: GenesisProfile = toml::from_str(&fs::read_to_string(path)?)?;
+        profile.validate()?;
+        Ok(profile)
+    }
+
+    /// Checks that the block windows of the profile are consistent
+    pub fn validate(&self) -> Result<(), Error> {
+        let windows = &self.block_windows;
+        if windows.registration_start > windows.registration_end
+            || windows.registration_end > windows.pre_stake_start
+            || windows.pre_stake_start > windows.pre_stake_end
+        {
+            return Err(Error::InvalidBlockWindows);
+        }
+        Ok(())
+    }
+
+    /// Estimates the unix timestamp in seconds of the PoS genesis block from
+    /// the timestamp in seconds of the final block, assuming the given
+    /// confirmations are mined at the estimated PoW block time
+    pub fn estimated_genesis_timestamp(
+        &self,
+        final_block_timestamp: u64,
+        confirmations: u32,
+    ) -> u64 {
+        final_block_timestamp + confirmations as u64 * self.pow_block_time_ms / 1000
+    }
+
+    /// Gets the registration window ending in the given final block
+    pub fn registration_window(&self, final_block: String) -> PoWRegistrationWindow {
+        PoWRegistrationWindow {
+            validator_start: self.block_windows.registration_start,
+            pre_stake_start: self.block_windows.pre_stake_start,
+            pre_stake_end: self.block_windows.pre_stake_end,
+            final_block,
+            confirmations: self.block_windows.block_confirmations,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimated_genesis_timestamp_is_in_seconds() {
+        let profile = GenesisProfile::for_network(NetworkPreset::Test).unwrap();
+        // 10 confirmations of one minute after the final block
+        assert_eq!(
+            profile.estimated_genesis_timestamp(1_700_000_000, 10),
+            1_700_000_600
+        );
+    }
+}
@@ -0,0 +1,345 @@
+//! Statistics and sanity checks of a genesis config
+//!
+//! The report summarizes the migrated accounts and the registered validators
+//! and stakers of a genesis config, and flags values that deserve a closer
+//! look before the genesis is used:
+//!
+//! - Validators holding more than the configured share of the total stake.
+//! - Validators without any stake delegated to them.
+//! - HTLCs timing out within the first epoch of the PoS chain.
+//! - Vesting contracts whose end time overflows.
+
+use std::collections::HashMap;
+
+use nimiq_genesis_builder::config::{GenesisConfig, GenesisVestingContract};
+use nimiq_keys::Address;
+use nimiq_primitives::{coin::Coin, policy::Policy};
+
+use crate::validator_stakes;
+
+/// Number of top holders listed in the report
+const TOP_HOLDERS: usize = 10;
+
+/// Gets the time at which a vesting contract is fully vested, or `None` if it
+/// overflows
+fn vesting_end(contract: &GenesisVestingContract) -> Option<u64> {
+    let step_amount = u64::from(contract.step_amount);
+    let steps = if step_amount == 0 {
+        0
+    } else {
+        u64::from(contract.total_amount).div_ceil(step_amount)
+    };
+    steps
+        .checked_mul(contract.time_step)
+        .and_then(|duration| contract.start_time.checked_add(duration))
+}
+
+/// Number and total balance of the accounts of a class
+#[derive(Clone, Debug, Default)]
+pub struct AccountClassSummary {
+    /// Number of accounts
+    pub count: usize,
+    /// Sum of the balances of the accounts
+    pub total_balance: Coin,
+}
+
+impl AccountClassSummary {
+    fn add(&mut self, balance: Coin) {
+        self.count += 1;
+        self.total_balance += balance;
+    }
+}
+
+/// Address holding a balance
+#[derive(Clone, Debug)]
+pub struct Holder {
+    /// Address of the holder
+    pub address: Address,
+    /// Balance held by the address
+    pub balance: Coin,
+}
+
+/// Stake of a registered validator
+#[derive(Clone, Debug)]
+pub struct ValidatorSummary {
+    /// Address of the validator
+    pub address: Address,
+    /// Deposit of the validator plus the stake delegated to it
+    pub stake: Coin,
+    /// Number of stakers delegating to the validator
+    pub stakers: usize,
+}
+
+/// Earliest and latest of a set of timestamps
+#[derive(Clone, Copy, Debug)]
+pub struct TimeRange {
+    /// Earliest timestamp
+    pub min: u64,
+    /// Latest timestamp
+    pub max: u64,
+}
+
+impl TimeRange {
+    fn extend(range: &mut Option<TimeRange>, timestamp: u64) {
+        *range = Some(match *range {
+            Some(TimeRange { min, max }) => TimeRange {
+                min: min.min(timestamp),
+                max: max.max(timestamp),
+            },
+            None => TimeRange {
+                min: timestamp,
+                max: timestamp,
+            },
+        });
+    }
+}
+
+/// Suspicious value found in a genesis config
+#[derive(Clone, Debug)]
+pub enum GenesisWarning {
+    /// A validator holds more than the configured share of the total stake
+    StakeShareExceeded {
+        /// Address of the validator
+        validator: Address,
+        /// Stake of the validator
+        stake: Coin,
+        /// Share of the total stake in percent
+        share_percent: u64,
+    },
+    /// No stake is delegated to a validator
+    ZeroStakeValidator(Address),
+    /// The time at which a vesting contract is fully vested overflows
+    VestingEndOverflow(Address),
+    /// An HTLC times out within the first epoch
+    HtlcTimeoutInFirstEpoch {
+        /// Address of the HTLC
+        address: Address,
+        /// Timeout of the HTLC
+        timeout: u64,
+    },
+}
+
+/// Statistics and warnings of a genesis config
+#[derive(Clone, Debug, Default)]
+pub struct GenesisReport {
+    /// Basic accounts
+    pub basic_accounts: AccountClassSummary,
+    /// Vesting contracts
+    pub vesting_accounts: AccountClassSummary,
+    /// HTLC contracts
+    pub htlc_accounts: AccountClassSummary,
+    /// Addresses with the largest balances, counting vesting contracts by
+    /// their owner
+    pub top_holders: Vec<Holder>,
+    /// Total stake of the registered validators
+    pub total_stake: Coin,
+    /// Registered validators sorted by descending stake
+    pub validators: Vec<ValidatorSummary>,
+    /// Range of the vesting start times
+    pub vesting_start: Option<TimeRange>,
+    /// Range of the times at which the vesting contracts are fully vested
+    pub vesting_end: Option<TimeRange>,
+    /// Range of the HTLC timeouts
+    pub htlc_timeout: Option<TimeRange>,
+    /// Suspicious values found
+    pub warnings: Vec<GenesisWarning>,
+}
+
+impl GenesisReport {
+    /// Computes the report of a genesis config. Validators holding more than
+    /// `max_stake_percent` percent of the total stake are flagged.
+    pub fn new(genesis_config: &GenesisConfig, max_stake_percent: u8) -> Self {
+        let mut report = GenesisReport::default();
+        let mut holdings: HashMap<Address, Coin> = HashMap::new();
+
+        for account in &genesis_config.basic_accounts {
+            report.basic_accounts.add(account.balance);
+            *holdings.entry(account.address.clone()).or_default() += account.balance;
+        }
+        for contract in &genesis_config.vesting_accounts {
+            report.vesting_accounts.add(contract.balance);
+            *holdings.entry(contract.owner.clone()).or_default() += contract.balance;
+
+            TimeRange::extend(&mut report.vesting_start, contract.start_time);
+            match vesting_end(contract) {
+                Some(end) => TimeRange::extend(&mut report.vesting_end, end),
+                None => report
+                    .warnings
+                    .push(GenesisWarning::VestingEndOverflow(contract.address.clone())),
+            }
+        }
+
+        // HTLC timeouts and the block separation time are in milliseconds,
+        // while the genesis timestamp has a precision of seconds.
+        let first_epoch_end = genesis_config
+            .timestamp
+            .map(|timestamp| timestamp.unix_timestamp() as u64 * 1000)
+            .unwrap_or_default()
+            + Policy::blocks_per_epoch() as u64 * Policy::BLOCK_SEPARATION_TIME;
+        for contract in &genesis_config.htlc_accounts {
+            report.htlc_accounts.add(contract.balance);
+            TimeRange::extend(&mut report.htlc_timeout, contract.timeout);
+            if contract.timeout < first_epoch_end {
+                report
+                    .warnings
+                    .push(GenesisWarning::HtlcTimeoutInFirstEpoch {
+                        address: contract.address.clone(),
+                        timeout: contract.timeout,
+                    });
+            }
+        }
+
+        let mut top_holders: Vec<Holder> = holdings
+            .into_iter()
+            .map(|(address, balance)| Holder { address, balance })
+            .collect();
+        top_holders.sort_by(|a, b| b.balance.cmp(&a.balance));
+        top_holders.truncate(TOP_HOLDERS);
+        report.top_holders = top_holders;
+
+        let stakes = validator_stakes(genesis_config);
+        let mut stakers: HashMap<&Address, usize> = HashMap::new();
+        for staker in &genesis_config.stakers {
+            *stakers.entry(&staker.delegation).or_default() += 1;
+        }
+        report.total_stake = stakes
+            .values()
+            .fold(Coin::ZERO, |total, stake| total + *stake);
+        let mut validators: Vec<ValidatorSummary> = genesis_config
+            .validators
+            .iter()
+            .map(|validator| ValidatorSummary {
+                address: validator.validator_address.clone(),
+                stake: stakes[&validator.validator_address],
+                stakers: stakers
+                    .get(&validator.validator_address)
+                    .copied()
+                    .unwrap_or_default(),
+            })
+            .collect();
+        validators.sort_by(|a, b| b.stake.cmp(&a.stake));
+
+        let total_stake = u64::from(report.total_stake) as u128;
+        for validator in &validators {
+            let stake = u64::from(validator.stake) as u128;
+            if total_stake > 0 && stake * 100 > total_stake * max_stake_percent as u128 {
+                report.warnings.push(GenesisWarning::StakeShareExceeded {
+                    validator: validator.address.clone(),
+                    stake: validator.stake,
+                    share_percent: (stake * 100 / total_stake) as u64,
+                });
+            }
+            if validator.stakers == 0 {
+                report.warnings.push(GenesisWarning::ZeroStakeValidator(
+                    validator.address.clone(),
+                ));
+            }
+        }
+        report.validators = validators;
+
+        report
+    }
+
+    /// Logs the report, with its warnings at warning level
+    pub fn log(&self) {
+        for (class, summary) in [
+            ("basic", &self.basic_accounts),
+            ("vesting", &self.vesting_accounts),
+            ("htlc", &self.htlc_accounts),
+        ] {
+            log::info!(
+                class,
+                count = summary.count,
+                total_balance = %summary.total_balance,
+                "Genesis accounts"
+            );
+        }
+        for (rank, holder) in self.top_holders.iter().enumerate() {
+            log::info!(
+                rank = rank + 1,
+                address = holder.address.to_user_friendly_address(),
+                balance = %holder.balance,
+                "Top holder"
+            );
+        }
+        if let Some(range) = self.vesting_start {
+            log::info!(min = range.min, max = range.max, "Vesting start times");
+        }
+        if let Some(range) = self.vesting_end {
+            log::info!(min = range.min, max = range.max, "Vesting end times");
+        }
+        if let Some(range) = self.htlc_timeout {
+            log::info!(min = range.min, max = range.max, "HTLC timeouts");
+        }
+
+        log::info!(
+            validators = self.validators.len(),
+            total_stake = %self.total_stake,
+            "Genesis validators"
+        );
+        for validator in &self.validators {
+            log::info!(
+                address = validator.address.to_user_friendly_address(),
+                stake = %validator.stake,
+                stakers = validator.stakers,
+                "Validator stake"
+            );
+        }
+
+        for warning in &self.warnings {
+            match warning {
+                GenesisWarning::StakeShareExceeded {
+                    validator,
+                    stake,
+                    share_percent,
+                } => log::warn!(
+                    validator = validator.to_user_friendly_address(),
+                    stake = %stake,
+                    share_percent,
+                    "Validator exceeds the maximum share of the total stake"
+                ),
+                GenesisWarning::ZeroStakeValidator(validator) => log::warn!(
+                    validator = validator.to_user_friendly_address(),
+                    "Validator has no stake delegated to it"
+                ),
+                GenesisWarning::VestingEndOverflow(address) => log::warn!(
+                    address = address.to_user_friendly_address(),
+                    "Vesting contract end time overflows"
+                ),
+                GenesisWarning::HtlcTimeoutInFirstEpoch { address, timeout } => log::warn!(
+                    address = address.to_user_friendly_address(),
+                    timeout,
+                    "HTLC times out within the first epoch"
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vesting_contract(start_time: u64, time_step: u64) -> GenesisVestingContract {
+        GenesisVestingContract {
+            address: Address::from([1u8; Address::SIZE]),
+            owner: Address::from([2u8; Address::SIZE]),
+            balance: Coin::from_u64_unchecked(1000),
+            start_time,
+            step_amount: Coin::from_u64_unchecked(100),
+            time_step,
+            total_amount: Coin::from_u64_unchecked(1000),
+        }
+    }
+
+    #[test]
+    fn vesting_end_adds_all_steps() {
+        assert_eq!(vesting_end(&vesting_contract(1000, 60)), Some(1600));
+    }
+
+    #[test]
+    fn vesting_end_overflow_is_detected() {
+        assert_eq!(vesting_end(&vesting_contract(1000, u64::MAX / 5)), None);
+        assert_eq!(vesting_end(&vesting_contract(u64::MAX, 1)), None);
+    }
+}
@@ -0,0 +1,169 @@
+//! Genesis stages
+//!
+//! The genesis generation is split into independent stages whose outputs are
+//! saved as TOML files in a stage directory:
+//!
+//! - The genesis profile (`profile.toml`).
+//! - The final PoW block (`final_block.toml`).
+//! - The migrated PoW accounts (`accounts.toml`).
+//! - The registered validators and stakers (`agents.toml`).
+//! - The history root (`history.toml`).
+//!
+//! Once every stage is saved, the genesis config can be assembled without RPC
+//! access, which allows fetching the stages on an online machine and
+//! assembling and verifying the genesis on an offline one.
+//!
+//! The accounts, agents and history stages record the final block they were
+//! fetched at, so stages fetched for different final blocks are not combined.
+
+use std::{fs, path::Path};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use nimiq_genesis_builder::config::{
+    GenesisAccount, GenesisHTLC, GenesisStaker, GenesisValidator, GenesisVestingContract,
+};
+use nimiq_hash::Blake2bHash;
+
+use crate::{
+    profile::GenesisProfile,
+    types::{Error, PoSRegisteredAgents},
+};
+
+/// Output of a genesis stage that is saved in a stage directory
+pub trait Stage: Serialize + DeserializeOwned {
+    /// Name of the file of the stage within the stage directory
+    const FILE_NAME: &'static str;
+
+    /// Reads the stage from the stage directory
+    fn read(dir: &Path) -> Result<Self, Error> {
+        let file = dir.join(Self::FILE_NAME);
+        let contents = fs::read_to_string(&file).map_err(|error| {
+            log::error!(?error, ?file, "Could not read genesis stage");
+            Error::IO(error)
+        })?;
+        Ok(toml::from_str(&contents)?)
+    }
+
+    /// Writes the stage to the stage directory, creating it if needed
+    fn write(&self, dir: &Path) -> Result<(), Error> {
+        fs::create_dir_all(dir)?;
+        Ok(fs::write(
+            dir.join(Self::FILE_NAME),
+            toml::to_string(self)?,
+        )?)
+    }
+}
+
+impl Stage for GenesisProfile {
+    const FILE_NAME: &'static str = "profile.toml";
+}
+
+/// Final block in the PoW chain along with the PoS genesis timestamp
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FinalBlockStage {
+    /// Hash of the final block
+    pub hash: String,
+    /// Block number of the final block
+    pub number: u32,
+    /// Timestamp of the final block
+    pub timestamp: u64,
+    /// Hash of the PoW genesis block
+    pub pow_genesis_hash: String,
+    /// Timestamp of the PoS genesis block
+    pub genesis_timestamp: u64,
+}
+
+impl FinalBlockStage {
+    /// Checks that the stage `name` was fetched at this final block
+    pub fn check_stage(
+        &self,
+        name: &'static str,
+        final_block_hash: &str,
+        final_block_number: u32,
+    ) -> Result<(), Error> {
+        if final_block_hash != self.hash || final_block_number != self.number {
+            log::error!(
+                stage = name,
+                expected_hash = self.hash,
+                expected_number = self.number,
+                found_hash = final_block_hash,
+                found_number = final_block_number,
+                "Genesis stage was fetched at a different final block"
+            );
+            return Err(Error::StageFinalBlockMismatch(name));
+        }
+        Ok(())
+    }
+}
+
+impl Stage for FinalBlockStage {
+    const FILE_NAME: &'static str = "final_block.toml";
+}
+
+/// PoW accounts migrated at the final block
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AccountsStage {
+    /// Hash of the final block the stage was fetched at
+    pub final_block_hash: String,
+    /// Block number of the final block the stage was fetched at
+    pub final_block_number: u32,
+    /// Basic accounts
+    pub basic_accounts: Vec<GenesisAccount>,
+    /// Vesting accounts
+    pub vesting_accounts: Vec<GenesisVestingContract>,
+    /// HTLC accounts
+    pub htlc_accounts: Vec<GenesisHTLC>,
+}
+
+impl Stage for AccountsStage {
+    const FILE_NAME: &'static str = "accounts.toml";
+}
+
+/// Validators and stakers registered in the PoW chain
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AgentsStage {
+    /// Hash of the final block the stage was fetched at
+    pub final_block_hash: String,
+    /// Block number of the final block the stage was fetched at
+    pub final_block_number: u32,
+    /// Registered validators
+    pub validators: Vec<GenesisValidator>,
+    /// Registered stakers
+    pub stakers: Vec<GenesisStaker>,
+}
+
+impl AgentsStage {
+    /// Builds the agents stage of the agents registered up to `final_block`
+    pub fn new(agents: PoSRegisteredAgents, final_block: &FinalBlockStage) -> Self {
+        AgentsStage {
+            final_block_hash: final_block.hash.clone(),
+            final_block_number: final_block.number,
+            validators: agents
+                .validators
+                .into_iter()
+                .map(|validator| validator.validator)
+                .collect(),
+            stakers: agents.stakers,
+        }
+    }
+}
+
+impl Stage for AgentsStage {
+    const FILE_NAME: &'static str = "agents.toml";
+}
+
+/// History root of the PoS genesis block
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HistoryStage {
+    /// Hash of the final block the stage was fetched at
+    pub final_block_hash: String,
+    /// Block number of the final block the stage was fetched at
+    pub final_block_number: u32,
+    /// History root
+    pub history_root: Blake2bHash,
+}
+
+impl Stage for HistoryStage {
+    const FILE_NAME: &'static str = "history.toml";
+}
@@ -1,6 +1,10 @@
+use std::path::PathBuf;
+
 use thiserror::Error;
 
+use nimiq_database::DatabaseProxy;
 use nimiq_genesis_builder::config::GenesisStaker;
+use nimiq_hash::Blake2bHash;
 use nimiq_state_migration::types::GenesisValidator;
 
 /// Error types that can be returned
@@ -15,12 +19,73 @@ pub enum Error {
     /// State migration error
     #[error("State migration error: {0}")]
     State(#[from] nimiq_state_migration::types::Error),
+    /// History migration error
+    #[error("History migration error: {0}")]
+    History(#[from] nimiq_history_migration::Error),
     /// Hex conversion error
     #[error("Failed to decode string as hex")]
     Hex(#[from] hex::FromHexError),
     /// Serialization error
     #[error("Serialization: {0}")]
     Serialization(#[from] toml::ser::Error),
+    /// Deserialization error
+    #[error("Deserialization: {0}")]
+    Deserialization(#[from] toml::de::Error),
+    /// Genesis builder error
+    #[error("Genesis builder error: {0}")]
+    GenesisBuilder(#[from] nimiq_genesis_builder::GenesisBuilderError),
+    /// Database error
+    #[error("Database error")]
+    Database,
+    /// The confirmation block is not mined yet
+    #[error("Confirmation block {0} is not mined yet")]
+    ConfirmationBlockNotMined(u32),
+    /// The genesis timestamp is before the confirmation block
+    #[error("Genesis timestamp {timestamp} is before the confirmation block timestamp {confirmation_timestamp}")]
+    TimestampBeforeConfirmation {
+        /// Timestamp of the PoS genesis block
+        timestamp: u64,
+        /// Timestamp of the confirmation block
+        confirmation_timestamp: u64,
+    },
+    /// Unsupported VRF seed derivation version
+    #[error("Unsupported VRF seed derivation version {0}")]
+    UnsupportedVrfSeedVersion(u8),
+    /// Invalid key derived for the VRF seed
+    #[error("Invalid key derived for the VRF seed")]
+    InvalidVrfSeedKey,
+    /// Invalid binary genesis file
+    #[error("Invalid binary genesis file")]
+    InvalidBinaryGenesis,
+    /// Binary genesis file checksum mismatch
+    #[error("Binary genesis file checksum mismatch")]
+    BinaryGenesisChecksumMismatch,
+    /// The genesis config rebuilt from a binary genesis file builds a different
+    /// genesis block
+    #[error(
+        "Genesis config rebuilt from binary genesis builds block {found} instead of {expected}"
+    )]
+    BinaryGenesisConfigMismatch {
+        /// Hash of the genesis block in the binary genesis file
+        expected: Blake2bHash,
+        /// Hash of the genesis block built from the rebuilt genesis config
+        found: Blake2bHash,
+    },
+    /// The signing key is not registered for any validator
+    #[error("Signing key is not registered for any validator")]
+    UnregisteredSigningKey,
+    /// Invalid attestation
+    #[error("Invalid attestation")]
+    InvalidAttestation,
+    /// Genesis config without a history root
+    #[error("Genesis config is missing the history root")]
+    MissingHistoryRoot,
+    /// A genesis stage was fetched at a different final block
+    #[error("Genesis stage {0} was fetched at a different final block")]
+    StageFinalBlockMismatch(&'static str),
+    /// Inconsistent block windows in a genesis profile
+    #[error("Inconsistent block windows in genesis profile")]
+    InvalidBlockWindows,
     /// Invalid time
     #[error("Invalid timestamp")]
     Timestamp(#[from] time::error::ComponentRange),
@@ -73,3 +138,21 @@ pub struct PoSRegisteredAgents {
     /// Registered PoS stakers
     pub stakers: Vec<GenesisStaker>,
 }
+
+/// Source of the history root of the PoS genesis block
+pub enum HistorySource {
+    /// Build the history store in the given database by replaying the PoW chain
+    Build(DatabaseProxy),
+    /// Import a history archive into the given empty database, checking that it
+    /// results in the expected history root
+    Archive {
+        /// Database where the history archive is imported
+        env: DatabaseProxy,
+        /// History archive file
+        file: PathBuf,
+        /// Expected history root of the archive
+        expected_root: Blake2bHash,
+    },
+    /// Use a known history root without building the history store
+    Precomputed(Blake2bHash),
+}
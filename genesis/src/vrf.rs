@@ -0,0 +1,189 @@
+//! Derivation of the VRF seed of the PoS genesis block
+//!
+//! The VRF seed is derived from the hash of the final PoW block. The
+//! derivation is versioned so a genesis can always be reproduced with the
+//! derivation it was generated with:
+//!
+//! - Version 0 (legacy): seeds `rand::StdRng` with the final block hash,
+//!   generates a key pair and signs the default VRF seed with it using the
+//!   same RNG. `StdRng` is not guaranteed to be stable across `rand` versions.
+//! - Version 1: derives the secret key as
+//!   `Blake2b("nimiq-genesis-vrf-seed" || 0x01 || "key" || final_block_hash)`
+//!   and seeds a `ChaCha20Rng` with
+//!   `Blake2b("nimiq-genesis-vrf-seed" || 0x01 || "rng" || final_block_hash)`
+//!   to sign the default VRF seed. `ChaCha20Rng` has a portable, stable
+//!   output for a given seed.
+//!
+//! The `vrf-seed` command of the genesis binary prints the VRF seed of a final
+//! block hash for a given version.
+//!
+//! The VRF seed is signed by a key pair derived from the final block hash. The
+//! unit tests pin the public key of that key pair for these final block hashes:
+//!
+//! | Final block hash   | Version | Public key                                                         |
+//! |--------------------|---------|--------------------------------------------------------------------|
+//! | `00` repeated      | 0       | `b9c6ee1630ef3e711144a648db06bbb2284f7274cfbee53ffcee503cc1a49200` |
+//! | `00` repeated      | 1       | `c12e5ebaaf20734c031d1d1b978c0331b76b13ee405240e54f7f61db8a1056a9` |
+//! | `000102..1f`       | 0       | `c135ca9568966a8e939067db46021786edcd979ec8e1962f71a2084c7a58d23a` |
+//! | `000102..1f`       | 1       | `b07f68fa1d5853aa6e4132d3213e8f2c3b52cc567bedd87b139f65ccba5f75ac` |
+
+use std::io::Write;
+
+use nimiq_hash::{Blake2bHash, Blake2bHasher, Hasher};
+use nimiq_keys::{KeyPair, PrivateKey, SecureGenerate};
+use nimiq_vrf::VrfSeed;
+use rand::{rngs::StdRng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+use crate::types::Error;
+
+/// Version of the VRF seed derivation used for new genesis blocks
+pub const VRF_SEED_VERSION: u8 = 1;
+
+/// Domain separation tag of the hash-based VRF seed derivation
+const VRF_SEED_DOMAIN: &[u8] = b"nimiq-genesis-vrf-seed";
+
+/// Hashes the final block hash within the domain of the given version and
+/// purpose
+fn derive_bytes(
+    version: u8,
+    purpose: &[u8],
+    final_block_hash: &Blake2bHash,
+) -> Result<[u8; 32], Error> {
+    let mut hasher = Blake2bHasher::default();
+    hasher.write_all(VRF_SEED_DOMAIN)?;
+    hasher.write_all(&[version])?;
+    hasher.write_all(purpose)?;
+    hasher.write_all(final_block_hash.as_slice())?;
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(hasher.finish().as_slice());
+    Ok(bytes)
+}
+
+/// Derives the VRF seed of the PoS genesis block from the final PoW block hash
+/// using the given derivation version
+pub fn derive_vrf_seed(version: u8, final_block_hash: &Blake2bHash) -> Result<VrfSeed, Error> {
+    match version {
+        0 => {
+            let mut seed = [0u8; 32];
+            seed.copy_from_slice(final_block_hash.as_slice());
+            let mut rng = StdRng::from_seed(seed);
+            Ok(VrfSeed::default().sign_next_with_rng(&KeyPair::generate(&mut rng), &mut rng))
+        }
+        1 => {
+            let private_key =
+                PrivateKey::from_bytes(&derive_bytes(version, b"key", final_block_hash)?)
+                    .map_err(|_| Error::InvalidVrfSeedKey)?;
+            let mut rng = ChaCha20Rng::from_seed(derive_bytes(version, b"rng", final_block_hash)?);
+            Ok(VrfSeed::default().sign_next_with_rng(&KeyPair::from(private_key), &mut rng))
+        }
+        _ => Err(Error::UnsupportedVrfSeedVersion(version)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Final block hash, derivation version and public key of the key pair
+    /// signing the VRF seed
+    const VECTORS: [([u8; 32], u8, &str); 4] = [
+        (
+            [0u8; 32],
+            0,
+            "b9c6ee1630ef3e711144a648db06bbb2284f7274cfbee53ffcee503cc1a49200",
+        ),
+        (
+            [0u8; 32],
+            1,
+            "c12e5ebaaf20734c031d1d1b978c0331b76b13ee405240e54f7f61db8a1056a9",
+        ),
+        (
+            SEQUENTIAL_HASH,
+            0,
+            "c135ca9568966a8e939067db46021786edcd979ec8e1962f71a2084c7a58d23a",
+        ),
+        (
+            SEQUENTIAL_HASH,
+            1,
+            "b07f68fa1d5853aa6e4132d3213e8f2c3b52cc567bedd87b139f65ccba5f75ac",
+        ),
+    ];
+
+    const SEQUENTIAL_HASH: [u8; 32] = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+        0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d,
+        0x1e, 0x1f,
+    ];
+
+    fn signing_key_pair(version: u8, final_block_hash: &Blake2bHash) -> KeyPair {
+        match version {
+            0 => {
+                let mut seed = [0u8; 32];
+                seed.copy_from_slice(final_block_hash.as_slice());
+                KeyPair::generate(&mut StdRng::from_seed(seed))
+            }
+            _ => KeyPair::from(
+                PrivateKey::from_bytes(&derive_bytes(version, b"key", final_block_hash).unwrap())
+                    .unwrap(),
+            ),
+        }
+    }
+
+    #[test]
+    fn vrf_seed_is_signed_by_the_derived_key() {
+        for (hash, version, public_key) in VECTORS {
+            let final_block_hash = Blake2bHash::from(hash);
+            let key_pair = signing_key_pair(version, &final_block_hash);
+            assert_eq!(hex::encode(key_pair.public.as_bytes()), public_key);
+
+            let vrf_seed = derive_vrf_seed(version, &final_block_hash).unwrap();
+            assert!(vrf_seed
+                .verify(&VrfSeed::default(), &key_pair.public)
+                .is_ok());
+        }
+    }
+
+    #[test]
+    fn derived_bytes_match_vectors() {
+        let zero_hash = Blake2bHash::from([0u8; 32]);
+        assert_eq!(
+            hex::encode(derive_bytes(1, b"key", &zero_hash).unwrap()),
+            "ef05ab374b419b5328e90b357380ad645f40d822bb58bea98edebd0bc3126931"
+        );
+        assert_eq!(
+            hex::encode(derive_bytes(1, b"rng", &zero_hash).unwrap()),
+            "72da53bec2bb90766f19c8984158284f76a637b5df2d17348c119e5770b24920"
+        );
+    }
+
+    #[test]
+    fn vrf_seed_is_deterministic() {
+        for (hash, version, _) in VECTORS {
+            let final_block_hash = Blake2bHash::from(hash);
+            assert_eq!(
+                derive_vrf_seed(version, &final_block_hash).unwrap(),
+                derive_vrf_seed(version, &final_block_hash).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn vrf_seed_depends_on_version_and_hash() {
+        let zero_hash = Blake2bHash::from([0u8; 32]);
+        let sequential_hash = Blake2bHash::from(SEQUENTIAL_HASH);
+        assert_ne!(
+            derive_vrf_seed(0, &zero_hash).unwrap(),
+            derive_vrf_seed(1, &zero_hash).unwrap()
+        );
+        assert_ne!(
+            derive_vrf_seed(1, &zero_hash).unwrap(),
+            derive_vrf_seed(1, &sequential_hash).unwrap()
+        );
+    }
+
+    #[test]
+    fn unsupported_version_is_rejected() {
+        assert!(derive_vrf_seed(VRF_SEED_VERSION + 1, &Blake2bHash::from([0u8; 32])).is_err());
+    }
+}
@@ -0,0 +1,313 @@
+//! Portable history archives
+//!
+//! A history archive contains the migrated history as the list of
+//! `ExtendedTransaction`s of each PoW block. It allows to populate a history
+//! store without any RPC access to the PoW chain.
+//!
+//! The archive is a gzip compressed stream with the following layout:
+//!
+//! - Magic bytes (`ARCHIVE_MAGIC`) and format version (`ARCHIVE_VERSION`).
+//! - One record per block with transactions: a `1` marker byte, the block
+//!   number, the number of transactions and each serialized transaction
+//!   prefixed by its length. All integers are big endian `u32`s.
+//! - One record per PoW block hash: a `2` marker byte, the block number and the
+//!   block hash. The hashes of the last `MAX_REORG_DEPTH` blocks up to the last
+//!   block with transactions are included, so a history migration resumed from
+//!   an imported history store can still detect PoW chain reorganizations.
+//! - A `0` marker byte followed by the Blake2b checksum of everything before it.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use flate2::{bufread::GzDecoder, write::GzEncoder, Compression};
+use nimiq_blockchain::HistoryStore;
+use nimiq_database::{
+    traits::{Database, ReadTransaction, WriteTransaction},
+    volatile::VolatileDatabase,
+    DatabaseProxy,
+};
+use nimiq_hash::{Blake2bHash, Blake2bHasher, Hasher};
+use nimiq_serde::{Deserialize, Serialize};
+use nimiq_transaction::extended_transaction::ExtendedTransaction;
+
+use crate::{database::DEFAULT_DB_MAX_TABLES, Error, BLOCK_HASHES_TABLE, MAX_REORG_DEPTH};
+
+/// Magic bytes at the start of every history archive
+const ARCHIVE_MAGIC: &[u8; 4] = b"NQHA";
+
+/// Version of the history archive format
+const ARCHIVE_VERSION: u8 = 2;
+
+/// Marker preceding a block record
+const BLOCK_MARKER: u8 = 1;
+
+/// Marker preceding a block hash record
+const BLOCK_HASH_MARKER: u8 = 2;
+
+/// Marker preceding the archive checksum
+const END_MARKER: u8 = 0;
+
+/// Decompressing reader of an archive file
+type ArchiveDecoder = GzDecoder<BufReader<File>>;
+
+/// Record of a history archive
+enum ArchiveRecord {
+    /// Block number and transactions of a block with transactions
+    Block(u32, Vec<ExtendedTransaction>),
+    /// Block number and hash of a PoW block
+    BlockHash(u32, Blake2bHash),
+}
+
+/// Writer that computes the checksum of everything written through it
+struct ChecksumWriter<W: Write> {
+    inner: W,
+    hasher: Blake2bHasher,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.hasher.write_all(bytes)?;
+        Ok(self.inner.write_all(bytes)?)
+    }
+
+    fn write_u32(&mut self, value: u32) -> Result<(), Error> {
+        self.write_all(&value.to_be_bytes())
+    }
+}
+
+/// Reader that computes the checksum of everything read through it
+struct ChecksumReader<R: Read> {
+    inner: R,
+    hasher: Blake2bHasher,
+}
+
+impl<R: Read> ChecksumReader<R> {
+    fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>, Error> {
+        let mut bytes = vec![0u8; len];
+        self.inner
+            .read_exact(&mut bytes)
+            .map_err(|_| Error::InvalidArchive)?;
+        self.hasher.write_all(&bytes)?;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, Error> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32, Error> {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(&self.read_bytes(4)?);
+        Ok(u32::from_be_bytes(bytes))
+    }
+
+    /// Reads the next record, or `None` once the end marker is reached
+    fn read_record(&mut self) -> Result<Option<ArchiveRecord>, Error> {
+        match self.read_u8()? {
+            BLOCK_MARKER => {
+                let block_number = self.read_u32()?;
+                let num_transactions = self.read_u32()?;
+                let mut ext_transactions = Vec::with_capacity(num_transactions as usize);
+                for _ in 0..num_transactions {
+                    let len = self.read_u32()?;
+                    let bytes = self.read_bytes(len as usize)?;
+                    let ext_transaction = ExtendedTransaction::deserialize_from_vec(&bytes)
+                        .map_err(|_| Error::InvalidArchive)?;
+                    if ext_transaction.block_number != block_number {
+                        return Err(Error::InvalidArchive);
+                    }
+                    ext_transactions.push(ext_transaction);
+                }
+                Ok(Some(ArchiveRecord::Block(block_number, ext_transactions)))
+            }
+            BLOCK_HASH_MARKER => {
+                let block_number = self.read_u32()?;
+                let mut hash = [0u8; Blake2bHash::SIZE];
+                hash.copy_from_slice(&self.read_bytes(Blake2bHash::SIZE)?);
+                Ok(Some(ArchiveRecord::BlockHash(
+                    block_number,
+                    Blake2bHash::from(hash),
+                )))
+            }
+            END_MARKER => Ok(None),
+            _ => Err(Error::InvalidArchive),
+        }
+    }
+}
+
+impl ChecksumReader<ArchiveDecoder> {
+    /// Reads the checksum at the end of the archive and compares it with the
+    /// checksum of the data read so far. Nothing may follow the checksum,
+    /// neither in the compressed stream nor in the file.
+    fn verify_checksum(mut self) -> Result<(), Error> {
+        let mut checksum = [0u8; Blake2bHash::SIZE];
+        self.inner
+            .read_exact(&mut checksum)
+            .map_err(|_| Error::InvalidArchive)?;
+        if self.hasher.finish() != Blake2bHash::from(checksum) {
+            return Err(Error::ArchiveChecksumMismatch);
+        }
+
+        let mut trailing = [0u8; 1];
+        if self.inner.read(&mut trailing)? != 0 || self.inner.into_inner().read(&mut trailing)? != 0
+        {
+            return Err(Error::InvalidArchive);
+        }
+        Ok(())
+    }
+}
+
+/// Opens an archive file and checks its header
+fn open_archive(file_path: &Path) -> Result<ChecksumReader<ArchiveDecoder>, Error> {
+    let mut reader = ChecksumReader {
+        inner: GzDecoder::new(BufReader::new(File::open(file_path)?)),
+        hasher: Blake2bHasher::default(),
+    };
+    if reader.read_bytes(ARCHIVE_MAGIC.len())? != ARCHIVE_MAGIC
+        || reader.read_u8()? != ARCHIVE_VERSION
+    {
+        return Err(Error::InvalidArchive);
+    }
+    Ok(reader)
+}
+
+/// Exports the history store in `env` to a history archive. Returns the history
+/// root of the exported history.
+///
+/// The hashes of the last `MAX_REORG_DEPTH` PoW blocks up to the last block with
+/// transactions must be stored along with the history.
+pub fn export_history(env: DatabaseProxy, file_path: &Path) -> Result<Blake2bHash, Error> {
+    let history_store = HistoryStore::new(env.clone());
+    let block_hashes = env.open_table(BLOCK_HASHES_TABLE.to_string());
+    let history_root = history_store
+        .get_history_tree_root(0, None)
+        .ok_or(Error::HistoryRootError)?;
+    let last_block_number = history_store
+        .get_last_leaf_block_number(None)
+        .ok_or(Error::HistoryRootError)?;
+
+    let mut writer = ChecksumWriter {
+        inner: GzEncoder::new(
+            BufWriter::new(File::create(file_path)?),
+            Compression::best(),
+        ),
+        hasher: Blake2bHasher::default(),
+    };
+    writer.write_all(ARCHIVE_MAGIC)?;
+    writer.write_all(&[ARCHIVE_VERSION])?;
+
+    for block_number in 1..=last_block_number {
+        let ext_transactions = history_store.get_block_transactions(block_number, None);
+        if ext_transactions.is_empty() {
+            continue;
+        }
+        writer.write_all(&[BLOCK_MARKER])?;
+        writer.write_u32(block_number)?;
+        writer.write_u32(ext_transactions.len() as u32)?;
+        for ext_transaction in ext_transactions {
+            let bytes = ext_transaction.serialize_to_vec();
+            writer.write_u32(bytes.len() as u32)?;
+            writer.write_all(&bytes)?;
+        }
+    }
+
+    let tail_start = last_block_number.saturating_sub(MAX_REORG_DEPTH - 1).max(1);
+    for block_number in tail_start..=last_block_number {
+        let block_hash: Blake2bHash = env
+            .read_transaction()
+            .get(&block_hashes, &block_number)
+            .ok_or(Error::MissingBlockHash(block_number))?;
+        writer.write_all(&[BLOCK_HASH_MARKER])?;
+        writer.write_u32(block_number)?;
+        writer.write_all(block_hash.as_bytes())?;
+    }
+
+    writer.write_all(&[END_MARKER])?;
+    let checksum = writer.hasher.finish();
+    let mut inner = writer.inner;
+    inner.write_all(checksum.as_bytes())?;
+    inner.finish()?.flush()?;
+
+    Ok(history_root)
+}
+
+/// Imports a history archive into the empty history store in `env`.
+///
+/// The archive is first imported into a scratch database. Only if the archive
+/// checksum is valid and the resulting history root matches `expected_root`,
+/// the history is written to the history store, in a single write transaction.
+/// The PoW block hashes of the archive are stored along with the history, as
+/// when migrating the history from the PoW chain.
+pub fn import_history(
+    env: DatabaseProxy,
+    file_path: &Path,
+    expected_root: &Blake2bHash,
+) -> Result<Blake2bHash, Error> {
+    let history_store = HistoryStore::new(env.clone());
+    if history_store.get_last_leaf_block_number(None).is_some() {
+        log::error!("History store is not empty");
+        return Err(Error::HistoryRootError);
+    }
+
+    let scratch_env = VolatileDatabase::new(DEFAULT_DB_MAX_TABLES).map_err(|error| {
+        log::error!(?error, "Failed to create scratch database");
+        Error::Database
+    })?;
+    let scratch_history_store = HistoryStore::new(scratch_env.clone());
+
+    let mut reader = open_archive(file_path)?;
+    let mut block_numbers = Vec::new();
+    let mut hashes = Vec::new();
+    while let Some(record) = reader.read_record()? {
+        match record {
+            ArchiveRecord::Block(block_number, ext_transactions) => {
+                log::trace!(block_number, "Importing block");
+                let mut txn = scratch_env.write_transaction();
+                scratch_history_store.add_to_history(&mut txn, 0, &ext_transactions);
+                txn.commit();
+                block_numbers.push(block_number);
+            }
+            ArchiveRecord::BlockHash(block_number, block_hash) => {
+                hashes.push((block_number, block_hash))
+            }
+        }
+    }
+    reader.verify_checksum()?;
+
+    // Resuming the history migration checks the hash of the last block with
+    // transactions against the PoW chain
+    if let Some(last_block_number) = block_numbers.last() {
+        if !hashes
+            .iter()
+            .any(|(block_number, _)| block_number == last_block_number)
+        {
+            return Err(Error::MissingBlockHash(*last_block_number));
+        }
+    }
+
+    let history_root = scratch_history_store
+        .get_history_tree_root(0, None)
+        .ok_or(Error::HistoryRootError)?;
+    if history_root != *expected_root {
+        return Err(Error::HistoryRootMismatch {
+            expected: expected_root.clone(),
+            found: history_root,
+        });
+    }
+
+    let block_hashes = env.open_table(BLOCK_HASHES_TABLE.to_string());
+    let mut txn = env.write_transaction();
+    for block_number in block_numbers {
+        let ext_transactions = scratch_history_store.get_block_transactions(block_number, None);
+        history_store.add_to_history(&mut txn, 0, &ext_transactions);
+    }
+    for (block_number, block_hash) in hashes {
+        txn.put(&block_hashes, &block_number, &block_hash);
+    }
+    txn.commit();
+
+    Ok(history_root)
+}
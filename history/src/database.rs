@@ -0,0 +1,122 @@
+//! Database environment shared by the migration binaries
+//!
+//! The history and genesis binaries, as well as the wrapper, must agree on the
+//! database used for the history store, so its name and environment settings
+//! are defined here.
+
+use std::path::{Path, PathBuf};
+
+use nimiq_database::{mdbx::MdbxDatabase, DatabaseProxy};
+use nimiq_primitives::networks::NetworkId;
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// Default maximum size of the database in GiB
+pub const DEFAULT_DB_SIZE_GIB: usize = 100;
+
+/// Default maximum number of named tables in the database
+pub const DEFAULT_DB_MAX_TABLES: u32 = 20;
+
+/// Default maximum number of concurrent readers of the database
+pub const DEFAULT_DB_MAX_READERS: u32 = 600;
+
+/// Network presets that can be migrated
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkPreset {
+    /// PoW MainNet migrated into PoS MainNet
+    Main,
+    /// PoW TestNet migrated into PoS TestNet
+    Test,
+    /// PoW DevNet migrated into PoS DevNet
+    Dev,
+}
+
+impl NetworkPreset {
+    /// Gets the preset of the PoS network with the given network ID
+    pub fn from_pos_network_id(network_id: NetworkId) -> Option<Self> {
+        match network_id {
+            NetworkId::MainAlbatross => Some(NetworkPreset::Main),
+            NetworkId::TestAlbatross => Some(NetworkPreset::Test),
+            NetworkId::DevAlbatross => Some(NetworkPreset::Dev),
+            _ => None,
+        }
+    }
+
+    /// Network ID of the PoW chain being migrated
+    pub fn pow_network_id(&self) -> NetworkId {
+        match self {
+            NetworkPreset::Main => NetworkId::Main,
+            NetworkPreset::Test => NetworkId::Test,
+            NetworkPreset::Dev => NetworkId::Dev,
+        }
+    }
+
+    /// Network ID of the resulting PoS chain
+    pub fn pos_network_id(&self) -> NetworkId {
+        match self {
+            NetworkPreset::Main => NetworkId::MainAlbatross,
+            NetworkPreset::Test => NetworkId::TestAlbatross,
+            NetworkPreset::Dev => NetworkId::DevAlbatross,
+        }
+    }
+
+    /// Name of the history database, which is the one the PoS client uses for
+    /// its history store.
+    pub fn history_db_name(&self) -> String {
+        let name = match self {
+            NetworkPreset::Main => "main",
+            NetworkPreset::Test => "test",
+            NetworkPreset::Dev => "dev",
+        };
+        format!("{name}-history-consensus")
+    }
+}
+
+/// Settings of the database environment
+#[derive(clap::Args, Clone, Debug)]
+pub struct DatabaseSettings {
+    /// Maximum size of the database in GiB
+    #[arg(long, default_value_t = DEFAULT_DB_SIZE_GIB)]
+    pub db_size_gib: usize,
+
+    /// Maximum number of named tables in the database
+    #[arg(long, default_value_t = DEFAULT_DB_MAX_TABLES)]
+    pub db_max_tables: u32,
+
+    /// Maximum number of concurrent readers of the database
+    #[arg(long, default_value_t = DEFAULT_DB_MAX_READERS)]
+    pub db_max_readers: u32,
+}
+
+impl Default for DatabaseSettings {
+    fn default() -> Self {
+        Self {
+            db_size_gib: DEFAULT_DB_SIZE_GIB,
+            db_max_tables: DEFAULT_DB_MAX_TABLES,
+            db_max_readers: DEFAULT_DB_MAX_READERS,
+        }
+    }
+}
+
+/// Opens (or creates) the history database of a network preset within the
+/// `db_path` directory. Returns the path of the database along with it.
+pub fn open_history_database(
+    db_path: &Path,
+    network: NetworkPreset,
+    settings: &DatabaseSettings,
+) -> Result<(PathBuf, DatabaseProxy), Error> {
+    let db_path = db_path.join(network.history_db_name());
+    let env = MdbxDatabase::new_with_max_readers(
+        db_path.clone(),
+        settings.db_size_gib * 1024 * 1024 * 1024,
+        settings.db_max_tables,
+        settings.db_max_readers,
+    )
+    .map_err(|error| {
+        log::error!(?error, ?db_path, "Failed to create database");
+        Error::Database
+    })?;
+    Ok((db_path, env))
+}
@@ -0,0 +1,152 @@
+//! PoW block dumps
+//!
+//! A block dump is a line-delimited JSON file with one PoW block per line, in
+//! ascending block number order and including the full transactions, as
+//! returned by `getBlockByNumber` when requesting transaction objects. It
+//! allows to build the history store on a machine without access to a PoW node.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Lines},
+    path::Path,
+    str::FromStr,
+};
+
+use nimiq_blockchain::HistoryStore;
+use nimiq_database::{
+    traits::{Database, ReadTransaction},
+    DatabaseProxy,
+};
+use nimiq_hash::Blake2bHash;
+use nimiq_migration_progress::{Phase, ProgressSubscriber, ProgressTracker};
+use nimiq_primitives::networks::NetworkId;
+use nimiq_rpc::primitives::{Block, TransactionSequence as PoWTransactionSequence};
+
+use crate::{
+    get_block_ext_transactions, store_block, Error, HashMismatchReport, BLOCK_HASHES_TABLE,
+};
+
+/// Reader of a PoW block dump file
+pub struct BlockDump {
+    lines: Lines<BufReader<File>>,
+    line_number: usize,
+}
+
+impl BlockDump {
+    /// Opens a block dump file
+    pub fn open(file_path: &Path) -> Result<Self, Error> {
+        Ok(Self {
+            lines: BufReader::new(File::open(file_path)?).lines(),
+            line_number: 0,
+        })
+    }
+
+    /// Reads the next block of the dump, or `None` at the end of the file
+    pub fn next_block(&mut self) -> Result<Option<Block>, Error> {
+        for line in self.lines.by_ref() {
+            self.line_number += 1;
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            return serde_json::from_str(&line)
+                .map(Some)
+                .map_err(|_| Error::InvalidDump(self.line_number));
+        }
+        Ok(None)
+    }
+}
+
+/// Gets the PoS genesis history root by reading all of the PoW blocks from a
+/// block dump and building a single history tree.
+///
+/// This behaves like `get_history_root`, except that the PoW blocks come from
+/// the dump instead of the PoW chain. When resuming from an existing history
+/// store, the hash of its last block must match the one in the dump.
+pub fn get_history_root_from_dump(
+    dump: &mut BlockDump,
+    cutting_pow_block_number: u32,
+    env: DatabaseProxy,
+    network_id: NetworkId,
+    mut mismatch_report: Option<&mut HashMismatchReport>,
+    progress: &dyn ProgressSubscriber,
+) -> Result<Blake2bHash, Error> {
+    let history_store = HistoryStore::new(env.clone());
+    let block_hashes = env.open_table(BLOCK_HASHES_TABLE.to_string());
+    let mut progress = ProgressTracker::new(
+        progress,
+        Phase::History,
+        Some(cutting_pow_block_number as u64),
+    );
+
+    let last_block_number = history_store.get_last_leaf_block_number(None);
+    if let Some(last_block_number) = last_block_number {
+        if last_block_number > cutting_pow_block_number {
+            log::error!("Found incompatible history store in the database");
+            return Err(Error::HistoryRootError);
+        }
+    }
+
+    let mut next_block_number = 1;
+    while next_block_number < cutting_pow_block_number {
+        let Some(block) = dump.next_block()? else {
+            log::error!(
+                block_number = next_block_number,
+                "Block dump ends before the cutting block"
+            );
+            return Err(Error::UnknownBlock);
+        };
+        if block.number != next_block_number {
+            return Err(Error::InvalidDump(dump.line_number));
+        }
+        next_block_number += 1;
+
+        // Skip the blocks that were already migrated
+        if let Some(last_block_number) = last_block_number {
+            if block.number == last_block_number {
+                let stored_hash: Option<Blake2bHash> =
+                    env.read_transaction().get(&block_hashes, &block.number);
+                if let Some(stored_hash) = stored_hash {
+                    if stored_hash != Blake2bHash::from_str(&block.hash)? {
+                        return Err(Error::DivergentBlock(block.number));
+                    }
+                }
+            }
+            if block.number <= last_block_number {
+                progress.skip_to(block.number as u64);
+                continue;
+            }
+        }
+
+        // The dump must contain the full transactions of every block
+        let pow_transactions = match &block.transactions {
+            PoWTransactionSequence::Transactions(pow_transactions) => pow_transactions.clone(),
+            PoWTransactionSequence::BlockHashes(hashes) if hashes.is_empty() => vec![],
+            PoWTransactionSequence::BlockHashes(_) => {
+                return Err(Error::InvalidDump(dump.line_number))
+            }
+        };
+        let ext_transactions = get_block_ext_transactions(
+            &block,
+            pow_transactions,
+            network_id,
+            mismatch_report.as_deref_mut(),
+        )?;
+
+        // Add transactions to the history store
+        store_block(
+            &env,
+            &history_store,
+            &block_hashes,
+            &block,
+            &ext_transactions,
+        )?;
+        progress.advance(1, ext_transactions.len() as u64);
+    }
+    progress.finish();
+
+    // Get history tree root
+    history_store
+        .get_history_tree_root(0, None)
+        .ok_or(Error::HistoryRootError)
+}
@@ -1,13 +1,19 @@
-use std::fmt::Write;
+pub mod archive;
+pub mod database;
+pub mod dump;
+pub mod query;
+
+use std::{fs, path::Path, str::FromStr};
 
-use indicatif::{HumanDuration, ProgressBar, ProgressState, ProgressStyle};
 use nimiq_blockchain::HistoryStore;
 use nimiq_database::{
-    traits::{Database, WriteTransaction},
-    DatabaseProxy,
+    traits::{Database, ReadTransaction, WriteTransaction},
+    volatile::VolatileDatabase,
+    DatabaseProxy, TableProxy,
 };
 use nimiq_hash::{Blake2bHash, Hash};
 use nimiq_keys::{Address, AddressParseError};
+use nimiq_migration_progress::{Phase, ProgressSubscriber, ProgressTracker};
 use nimiq_primitives::{
     account::AccountType,
     coin::{Coin, CoinConvertError},
@@ -15,7 +21,7 @@ use nimiq_primitives::{
 };
 use nimiq_rpc::{
     primitives::{
-        TransactionDetails as PoWTransaction, TransactionSequence as PoWTransactionSequence,
+        Block, TransactionDetails as PoWTransaction, TransactionSequence as PoWTransactionSequence,
     },
     Client,
 };
@@ -33,6 +39,9 @@ pub enum Error {
     /// Unknown PoW block
     #[error("Unknown PoW block")]
     UnknownBlock,
+    /// Unknown transaction
+    #[error("Unknown transaction")]
+    UnknownTransaction,
     /// Address parsing error
     #[error("Failed to parse Nimiq address")]
     Address(#[from] AddressParseError),
@@ -48,11 +57,119 @@ pub enum Error {
     /// Error calculating history root
     #[error("History root error")]
     HistoryRootError,
+    /// The PoS re-encoding of a PoW transaction doesn't reproduce its hash
+    #[error("Transaction hash mismatch in block {block_number}: {hash}")]
+    TransactionHashMismatch {
+        /// Block number of the block containing the transaction
+        block_number: u32,
+        /// Hash of the transaction in the PoW chain
+        hash: String,
+    },
+    /// A PoW transaction belongs to a different network than the one being migrated
+    #[error("Transaction {hash} in block {block_number} has network ID {found:?}, expected {expected:?}")]
+    NetworkIdMismatch {
+        /// Block number of the block containing the transaction
+        block_number: u32,
+        /// Hash of the transaction in the PoW chain
+        hash: String,
+        /// Network ID of the network being migrated
+        expected: NetworkId,
+        /// Network ID found in the transaction
+        found: NetworkId,
+    },
+    /// The history store diverges from the PoW chain at this block number
+    #[error("History store diverges from the PoW chain at block {0}")]
+    DivergentBlock(u32),
+    /// The history root doesn't match the expected one
+    #[error("History root mismatch: expected {expected}, found {found}")]
+    HistoryRootMismatch {
+        /// Expected history root
+        expected: Blake2bHash,
+        /// History root found
+        found: Blake2bHash,
+    },
+    /// Database error
+    #[error("Database error")]
+    Database,
+    /// Malformed PoW block dump
+    #[error("Invalid PoW block dump at line {0}")]
+    InvalidDump(usize),
+    /// Malformed history archive
+    #[error("Invalid history archive")]
+    InvalidArchive,
+    /// The history archive checksum doesn't match its contents
+    #[error("History archive checksum mismatch")]
+    ArchiveChecksumMismatch,
+    /// The hash of a migrated PoW block is not stored in the history database
+    #[error("Missing hash of PoW block {0}")]
+    MissingBlockHash(u32),
+    /// The PoW chain reorganized deeper than what can be rolled back
+    #[error("PoW chain reorganization deeper than {0} blocks")]
+    ReorgTooDeep(u32),
+    /// IO error
+    #[error("I/O error: {0}")]
+    IO(#[from] std::io::Error),
+}
+
+/// PoW transaction whose PoS re-encoding doesn't reproduce the PoW hash
+#[derive(Clone, Debug)]
+pub struct HashMismatch {
+    /// Block number of the block containing the transaction
+    pub block_number: u32,
+    /// Hash of the transaction in the PoW chain
+    pub pow_hash: String,
+    /// Hash of the transaction once converted to PoS
+    pub pos_hash: String,
 }
 
+/// Collects the transactions with a hash mismatch instead of aborting the
+/// history migration on the first one.
+///
+/// Transactions recorded here are quarantined: they are not added to the
+/// history store, so the resulting history root must not be used while the
+/// report is not empty.
+#[derive(Debug, Default)]
+pub struct HashMismatchReport {
+    /// Transactions found with a hash mismatch
+    pub mismatches: Vec<HashMismatch>,
+}
+
+impl HashMismatchReport {
+    /// Returns true if no mismatch was recorded
+    pub fn is_empty(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    /// Writes the report to a file, one mismatch per line
+    pub fn write(&self, file_path: &Path) -> Result<(), Error> {
+        let report: String = self
+            .mismatches
+            .iter()
+            .map(|mismatch| {
+                format!(
+                    "{} {} {}\n",
+                    mismatch.block_number, mismatch.pow_hash, mismatch.pos_hash
+                )
+            })
+            .collect();
+        Ok(fs::write(file_path, report)?)
+    }
+}
+
+/// Maximum depth of a PoW chain reorganization that can be rolled back when
+/// resuming the history migration.
+pub(crate) const MAX_REORG_DEPTH: u32 = 100;
+
+/// Name of the table that stores the hashes of the PoW blocks already migrated
+/// to the history store.
+pub(crate) const BLOCK_HASHES_TABLE: &str = "PoWBlockHashes";
+
 fn from_pow_network_id(pow_network_id: u8) -> Result<NetworkId, Error> {
     match pow_network_id {
         1u8 => Ok(NetworkId::Test),
+        2u8 => Ok(NetworkId::Dev),
+        3u8 => Ok(NetworkId::Bounty),
+        4u8 => Ok(NetworkId::Dummy),
         42u8 => Ok(NetworkId::Main),
         _ => Err(Error::InvalidValue),
     }
@@ -103,26 +220,194 @@ fn from_pow_transaction(pow_transaction: &PoWTransaction) -> Result<Transaction,
     Ok(tx)
 }
 
+/// Gets the transactions of a PoW block, fetching them from the PoW chain if
+/// the block only contains their hashes.
+async fn get_block_pow_transactions(
+    client: &Client,
+    block: &Block,
+) -> Result<Vec<PoWTransaction>, Error> {
+    match &block.transactions {
+        PoWTransactionSequence::BlockHashes(hashes) => {
+            let mut pow_transactions = vec![];
+            for hash in hashes {
+                log::trace!(hash, "Fetching transaction");
+                pow_transactions.push(client.get_transaction_by_hash(hash).await?);
+            }
+            Ok(pow_transactions)
+        }
+        PoWTransactionSequence::Transactions(pow_transactions) => Ok(pow_transactions.clone()),
+    }
+}
+
+/// Converts the transactions of a PoW block into the PoS extended transactions
+/// that represent this block in the history store.
+fn get_block_ext_transactions(
+    block: &Block,
+    pow_transactions: Vec<PoWTransaction>,
+    network_id: NetworkId,
+    mut mismatch_report: Option<&mut HashMismatchReport>,
+) -> Result<Vec<ExtendedTransaction>, Error> {
+    let block_height = block.number;
+    let mut transactions = vec![];
+    for pow_transaction in pow_transactions {
+        log::trace!(hash = pow_transaction.hash, "Processing transaction");
+        let pos_transaction = from_pow_transaction(&pow_transaction)?;
+        if pos_transaction.network_id != network_id {
+            return Err(Error::NetworkIdMismatch {
+                block_number: block_height,
+                hash: pow_transaction.hash,
+                expected: network_id,
+                found: pos_transaction.network_id,
+            });
+        }
+
+        let pos_hash = pos_transaction.hash::<Blake2bHash>().to_hex();
+        if pow_transaction.hash != pos_hash {
+            log::warn!(
+                block_number = block_height,
+                pow_hash = pow_transaction.hash,
+                pos_hash,
+                "Transaction hash mismatch"
+            );
+            match mismatch_report.as_deref_mut() {
+                Some(report) => {
+                    report.mismatches.push(HashMismatch {
+                        block_number: block_height,
+                        pow_hash: pow_transaction.hash,
+                        pos_hash,
+                    });
+                    continue;
+                }
+                None => {
+                    return Err(Error::TransactionHashMismatch {
+                        block_number: block_height,
+                        hash: pow_transaction.hash,
+                    })
+                }
+            }
+        }
+        transactions.push(ExecutedTransaction::Ok(pos_transaction));
+    }
+
+    Ok(ExtendedTransaction::from(
+        network_id,
+        block_height,
+        block.timestamp.into(),
+        transactions,
+        [].to_vec(),
+    ))
+}
+
+/// Adds the extended transactions of a PoW block to the history store and keeps
+/// track of the block hash.
+fn store_block(
+    env: &DatabaseProxy,
+    history_store: &HistoryStore,
+    block_hashes: &TableProxy,
+    block: &Block,
+    ext_transactions: &[ExtendedTransaction],
+) -> Result<(), Error> {
+    let block_hash = Blake2bHash::from_str(&block.hash)?;
+    let mut txn = env.write_transaction();
+    if !ext_transactions.is_empty() {
+        history_store.add_to_history(&mut txn, 0, ext_transactions);
+    }
+    txn.put(block_hashes, &block.number, &block_hash);
+    txn.commit();
+    Ok(())
+}
+
+/// Rolls the history store back to the last block whose hash still matches the
+/// PoW chain, starting from `last_block_number`. Returns the block number of the
+/// new tail of the history store.
+async fn rollback_to_fork_point(
+    client: &Client,
+    env: &DatabaseProxy,
+    history_store: &HistoryStore,
+    block_hashes: &TableProxy,
+    last_block_number: u32,
+) -> Result<u32, Error> {
+    let tail_start = last_block_number.saturating_sub(MAX_REORG_DEPTH - 1).max(1);
+    let mut fork_point = None;
+    let mut found_stored_hash = false;
+    for block_number in (tail_start..=last_block_number).rev() {
+        let stored_hash: Option<Blake2bHash> =
+            env.read_transaction().get(block_hashes, &block_number);
+        let Some(stored_hash) = stored_hash else {
+            continue;
+        };
+        found_stored_hash = true;
+        let block = client.get_block_by_number(block_number, false).await?;
+        if Blake2bHash::from_str(&block.hash)? == stored_hash {
+            fork_point = Some(block_number);
+            break;
+        }
+        log::warn!(block_number, "Stored block hash differs from the PoW chain");
+    }
+
+    let fork_point = match fork_point {
+        Some(fork_point) => fork_point,
+        None if !found_stored_hash => {
+            // History stores written before block hashes were tracked can't be checked
+            log::warn!("No block hashes stored, can't check for PoW chain reorganizations");
+            return Ok(last_block_number);
+        }
+        None => return Err(Error::ReorgTooDeep(MAX_REORG_DEPTH)),
+    };
+    if fork_point == last_block_number {
+        return Ok(last_block_number);
+    }
+
+    // Remove every leaf and block hash past the fork point
+    log::warn!(
+        fork_point,
+        last_block_number,
+        "PoW chain reorganized, rolling back history store"
+    );
+    let mut num_leaves = 0;
+    for block_number in fork_point + 1..=last_block_number {
+        num_leaves += history_store
+            .get_block_transactions(block_number, None)
+            .len();
+    }
+    let mut txn = env.write_transaction();
+    history_store.remove_partial_history(&mut txn, 0, num_leaves);
+    for block_number in fork_point + 1..=last_block_number {
+        txn.remove(block_hashes, &block_number);
+    }
+    txn.commit();
+
+    Ok(fork_point)
+}
+
 /// Gets the PoS genesis history root by getting all of the transactions from the
 /// PoW chain and building a single history tree.
+///
+/// If a `mismatch_report` is supplied, transactions whose PoS hash doesn't match
+/// their PoW hash are recorded there and skipped. Otherwise the first mismatch
+/// aborts the migration with `Error::TransactionHashMismatch`.
+///
+/// Every transaction must belong to the PoW network identified by `network_id`,
+/// otherwise `Error::NetworkIdMismatch` is returned.
+///
+/// The hashes of the processed PoW blocks are stored along with the history.
+/// When resuming from an existing history store they are compared against the
+/// PoW chain and, if it reorganized, the history store is rolled back to the fork
+/// point before continuing.
 pub async fn get_history_root(
     client: &Client,
     cutting_pow_block_number: u32,
     env: DatabaseProxy,
+    network_id: NetworkId,
+    mut mismatch_report: Option<&mut HashMismatchReport>,
+    progress: &dyn ProgressSubscriber,
 ) -> Result<Blake2bHash, Error> {
     let history_store = HistoryStore::new(env.clone());
-
-    // Setup progress bar
-    let pb = ProgressBar::new(cutting_pow_block_number as u64);
-    pb.set_style(
-        ProgressStyle::with_template(
-            "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] Block: {pos}, {percent}% (~{eta} remaining)",
-        )
-        .unwrap()
-        .with_key("eta", |state: &ProgressState, w: &mut dyn Write| {
-            write!(w, "{}", HumanDuration(state.eta())).unwrap()
-        })
-        .progress_chars("#>-"),
+    let block_hashes = env.open_table(BLOCK_HASHES_TABLE.to_string());
+    let mut progress = ProgressTracker::new(
+        progress,
+        Phase::History,
+        Some(cutting_pow_block_number as u64),
     );
 
     // We might have already some work done. Check if the database already content
@@ -137,57 +422,36 @@ pub async fn get_history_root(
                 return Err(Error::HistoryRootError);
             }
             // If there was already a history tree that we can use, continue with
-            // the next block
-            block_height + 1
+            // the next block after the point where it still matches the PoW chain
+            rollback_to_fork_point(client, &env, &history_store, &block_hashes, block_height)
+                .await?
+                + 1
         }
         // If there is no history tree, start from the genesis
         None => 1,
     };
+    progress.skip_to(start as u64 - 1);
 
     // Get transactions of each block and add them to the PoS history store
     for block_height in start..cutting_pow_block_number {
-        // Refresh the progress bar position
-        pb.set_position(block_height as u64);
-
         // Get all transactions for this block height
-        let mut transactions = vec![];
         let block = client.get_block_by_number(block_height, false).await?;
-        let mut network_id = NetworkId::Main;
-        match block.transactions {
-            PoWTransactionSequence::BlockHashes(hashes) => {
-                if hashes.is_empty() {
-                    continue;
-                }
-                for hash in hashes {
-                    log::trace!(hash, "Processing transaction");
-                    let pow_transaction = client.get_transaction_by_hash(&hash).await?;
-                    let pos_transaction = from_pow_transaction(&pow_transaction)?;
-                    network_id = pos_transaction.network_id;
-
-                    assert_eq!(
-                        pow_transaction.hash,
-                        pos_transaction.hash::<Blake2bHash>().to_hex()
-                    );
-                    transactions.push(ExecutedTransaction::Ok(pos_transaction));
-                }
-            }
-            PoWTransactionSequence::Transactions(_) => panic!("Unexpected transaction type"),
-        }
+        let pow_transactions = get_block_pow_transactions(client, &block).await?;
+        let ext_transactions = get_block_ext_transactions(
+            &block,
+            pow_transactions,
+            network_id,
+            mismatch_report.as_deref_mut(),
+        )?;
 
         // Add transactions to the history store
-        let mut txn = env.write_transaction();
-        history_store.add_to_history(
-            &mut txn,
-            0,
-            &ExtendedTransaction::from(
-                network_id,
-                block_height,
-                block.timestamp.into(),
-                transactions,
-                [].to_vec(),
-            ),
-        );
-        txn.commit();
+        store_block(
+            &env,
+            &history_store,
+            &block_hashes,
+            &block,
+            &ext_transactions,
+        )?;
     }
 
     // Get history tree root
@@ -195,3 +459,93 @@ pub async fn get_history_root(
         .get_history_tree_root(0, None)
         .ok_or(Error::HistoryRootError)
 }
+
+/// Verifies an existing history store against the PoW chain.
+///
+/// The transactions of each block are re-derived from the PoW chain and compared
+/// leaf by leaf with the ones stored for that block height. The history root is
+/// then recomputed from the re-derived transactions and compared with the stored
+/// one. Returns the verified history root or `Error::DivergentBlock` with the
+/// first block that doesn't match.
+pub async fn verify_history(
+    client: &Client,
+    cutting_pow_block_number: u32,
+    env: DatabaseProxy,
+    network_id: NetworkId,
+    progress: &dyn ProgressSubscriber,
+) -> Result<Blake2bHash, Error> {
+    let history_store = HistoryStore::new(env);
+
+    // The history root is recomputed in a scratch database
+    let scratch_env = VolatileDatabase::new(database::DEFAULT_DB_MAX_TABLES).map_err(|error| {
+        log::error!(?error, "Failed to create scratch database");
+        Error::Database
+    })?;
+    let scratch_history_store = HistoryStore::new(scratch_env.clone());
+
+    let mut progress = ProgressTracker::new(
+        progress,
+        Phase::HistoryVerification,
+        Some(cutting_pow_block_number as u64),
+    );
+
+    match history_store.get_last_leaf_block_number(None) {
+        Some(block_height) if block_height < cutting_pow_block_number => {}
+        Some(block_height) => {
+            log::error!(
+                last_block_number = block_height,
+                "History store contains blocks past the cutting block"
+            );
+            return Err(Error::DivergentBlock(cutting_pow_block_number));
+        }
+        None => {
+            log::error!("History store is empty");
+            return Err(Error::HistoryRootError);
+        }
+    }
+
+    for block_height in 1..cutting_pow_block_number {
+        let block = client.get_block_by_number(block_height, false).await?;
+        let pow_transactions = get_block_pow_transactions(client, &block).await?;
+        let expected = get_block_ext_transactions(&block, pow_transactions, network_id, None)?;
+        let stored = history_store.get_block_transactions(block_height, None);
+
+        let leaves_match = expected.len() == stored.len()
+            && expected
+                .iter()
+                .zip(stored.iter())
+                .all(|(expected, stored)| {
+                    expected.hash::<Blake2bHash>() == stored.hash::<Blake2bHash>()
+                });
+        if !leaves_match {
+            log::error!(
+                block_number = block_height,
+                expected_leaves = expected.len(),
+                stored_leaves = stored.len(),
+                "Found divergent block"
+            );
+            return Err(Error::DivergentBlock(block_height));
+        }
+        if !expected.is_empty() {
+            let mut txn = scratch_env.write_transaction();
+            scratch_history_store.add_to_history(&mut txn, 0, &expected);
+            txn.commit();
+        }
+        progress.advance(1, expected.len() as u64);
+    }
+    progress.finish();
+
+    let stored_root = history_store
+        .get_history_tree_root(0, None)
+        .ok_or(Error::HistoryRootError)?;
+    let computed_root = scratch_history_store
+        .get_history_tree_root(0, None)
+        .ok_or(Error::HistoryRootError)?;
+    if stored_root != computed_root {
+        return Err(Error::HistoryRootMismatch {
+            expected: computed_root,
+            found: stored_root,
+        });
+    }
+    Ok(computed_root)
+}
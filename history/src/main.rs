@@ -1,26 +1,138 @@
-use std::{path::Path, time::Instant};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Instant,
+};
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use log::level_filters::LevelFilter;
-use nimiq_database::mdbx::MdbxDatabase;
+use nimiq_database::DatabaseProxy;
+use nimiq_hash::Blake2bHash;
+use nimiq_keys::Address;
+use nimiq_migration_progress::TerminalProgress;
 use nimiq_rpc::Client;
+use nimiq_serde::Serialize;
 use tracing_subscriber::{filter::Targets, layer::SubscriberExt, util::SubscriberInitExt, Layer};
 use url::Url;
 
-use nimiq_history_migration::get_history_root;
+use nimiq_history_migration::{
+    archive::{export_history, import_history},
+    database::{open_history_database, DatabaseSettings, NetworkPreset},
+    dump::{get_history_root_from_dump, BlockDump},
+    get_history_root,
+    query::HistoryQuery,
+    verify_history, Error, HashMismatchReport,
+};
 
 /// Command line arguments for the binary
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Operations supported by the binary
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Builds the history store from the PoW chain
+    Build {
+        #[command(flatten)]
+        source: SourceArgs,
+
+        /// Optional file where transactions with a hash mismatch are reported.
+        /// If set, the migration continues past mismatched transactions.
+        #[arg(short, long)]
+        mismatch_report: Option<String>,
+    },
+    /// Builds the history store from a PoW block dump file
+    BuildFromDump {
+        /// Line-delimited JSON file with the PoW blocks and their transactions
+        #[arg(short, long)]
+        file: String,
+
+        /// Cutting block height to use
+        #[arg(short, long)]
+        height: u32,
+
+        #[command(flatten)]
+        db: DatabaseArgs,
+
+        /// Optional file where transactions with a hash mismatch are reported.
+        /// If set, the migration continues past mismatched transactions.
+        #[arg(short, long)]
+        mismatch_report: Option<String>,
+    },
+    /// Verifies an existing history store against the PoW chain
+    Verify {
+        #[command(flatten)]
+        source: SourceArgs,
+    },
+    /// Exports the history store to a history archive
+    Export {
+        #[command(flatten)]
+        db: DatabaseArgs,
+
+        /// History archive output file name
+        #[arg(short, long)]
+        file: String,
+    },
+    /// Looks up migrated transactions in the history store
+    Query {
+        #[command(flatten)]
+        db: DatabaseArgs,
+
+        #[command(flatten)]
+        target: QueryTarget,
+
+        /// Maximum number of transactions to return when looking up an address
+        #[arg(long, default_value_t = 100)]
+        max: u16,
+
+        /// Build an inclusion proof against the history root for the transaction
+        /// looked up by hash
+        #[arg(long, requires = "tx_hash")]
+        prove: bool,
+    },
+    /// Imports a history archive into an empty history store
+    Import {
+        #[command(flatten)]
+        db: DatabaseArgs,
+
+        /// History archive input file name
+        #[arg(short, long)]
+        file: String,
+
+        /// Expected history root of the imported history
+        #[arg(short, long)]
+        expected_root: String,
+    },
+}
+
+/// Arguments of a query over the history store
+#[derive(clap::Args, Debug)]
+#[group(required = true, multiple = false)]
+struct QueryTarget {
+    /// Hash of the transaction to look up
+    #[arg(long)]
+    tx_hash: Option<String>,
+
+    /// Address whose transactions are looked up
+    #[arg(long)]
+    address: Option<String>,
+
+    /// Block number whose transactions are looked up
+    #[arg(long)]
+    block_number: Option<u32>,
+}
+
+/// Arguments identifying the PoW chain and the history store database
+#[derive(clap::Args, Debug)]
+struct SourceArgs {
     /// RPC connection URL to use
     #[arg(short, long)]
     rpc: String,
 
-    /// TOML output file name
-    #[arg(short, long)]
-    db_path: String,
-
     /// Cutting block height to use
     #[arg(short, long)]
     height: u32,
@@ -29,9 +141,23 @@ struct Args {
     #[arg(short, long)]
     hash: String,
 
-    /// Set to true for testnet usage
+    #[command(flatten)]
+    db: DatabaseArgs,
+}
+
+/// Arguments identifying the history store database
+#[derive(clap::Args, Debug)]
+struct DatabaseArgs {
+    /// Path to the history store database
     #[arg(short, long)]
-    testnet: bool,
+    db_path: String,
+
+    /// Network to migrate
+    #[arg(short, long, value_enum)]
+    network: NetworkPreset,
+
+    #[command(flatten)]
+    settings: DatabaseSettings,
 }
 
 fn initialize_logging() {
@@ -46,10 +172,21 @@ fn initialize_logging() {
         .init();
 }
 
-#[tokio::main]
-async fn main() {
-    let args = Args::parse();
-    let url = match Url::parse(&args.rpc) {
+/// Opens the history store database
+fn open_database(db: &DatabaseArgs) -> (PathBuf, DatabaseProxy) {
+    match open_history_database(Path::new(&db.db_path), db.network, &db.settings) {
+        Ok(database) => database,
+        Err(e) => {
+            log::error!(error = ?e, "Failed to create database");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Connects to the PoW chain, checks that the cutting block exists and opens
+/// the history store database.
+async fn setup(source: &SourceArgs) -> (Client, PathBuf, DatabaseProxy) {
+    let url = match Url::parse(&source.rpc) {
         Ok(url) => url,
         Err(error) => {
             log::error!(?error, "Invalid RPC URL");
@@ -58,51 +195,289 @@ async fn main() {
     };
     let client = Client::new(url);
 
-    initialize_logging();
-
     // Get block according to arguments and check if it exists
-    let block = client.get_block_by_hash(&args.hash, false).await.unwrap();
-    if block.number != args.height {
+    let block = client.get_block_by_hash(&source.hash, false).await.unwrap();
+    if block.number != source.height {
         log::error!(
-            height = args.height,
-            hash = args.hash,
+            height = source.height,
+            hash = source.hash,
             "Could not find provided block"
         );
         std::process::exit(1);
     }
 
-    // Create DB environment
-    let network_id = if args.testnet { "test" } else { "main" };
-    let db_name = format!("{network_id}-history-consensus").to_lowercase();
-    let db_path = Path::new(&args.db_path).join(db_name);
-    let env = match MdbxDatabase::new_with_max_readers(
-        db_path.clone(),
-        100 * 1024 * 1024 * 1024,
-        20,
-        600,
-    ) {
-        Ok(db) => db,
+    let (db_path, env) = open_database(&source.db);
+
+    (client, db_path, env)
+}
+
+/// Writes the mismatch report if requested and logs the result of building the
+/// history tree.
+fn finish_build(
+    result: Result<Blake2bHash, Error>,
+    mismatch_report_file: Option<String>,
+    mismatch_report: Option<HashMismatchReport>,
+    start: Instant,
+) {
+    if let (Some(file), Some(report)) = (&mismatch_report_file, &mismatch_report) {
+        if let Err(error) = report.write(Path::new(file)) {
+            log::error!(?error, file, "Could not write mismatch report");
+            std::process::exit(1);
+        }
+        if !report.is_empty() {
+            log::error!(
+                mismatches = report.mismatches.len(),
+                file,
+                "Found transactions with a hash mismatch, the history root is not valid"
+            );
+            std::process::exit(1);
+        }
+    }
+
+    match result {
+        Ok(history_root) => {
+            let duration = start.elapsed();
+            log::info!(
+                duration = humantime::format_duration(duration).to_string(),
+                history_root = history_root.to_hex(),
+                "Finished building history tree"
+            )
+        }
         Err(e) => {
-            log::error!(error = ?e, "Failed to create database");
+            log::error!(error = ?e, "Failed to build history root");
             std::process::exit(1);
         }
-    };
+    }
+}
+
+async fn build(source: SourceArgs, mismatch_report_file: Option<String>) {
+    let (client, db_path, env) = setup(&source).await;
 
     // Build history tree
     log::info!(?db_path, "Building history tree");
     let start = Instant::now();
-    match get_history_root(&client, block.number, env).await {
+    let mut mismatch_report = mismatch_report_file
+        .as_ref()
+        .map(|_| HashMismatchReport::default());
+    let result = get_history_root(
+        &client,
+        source.height,
+        env,
+        source.db.network.pow_network_id(),
+        mismatch_report.as_mut(),
+        &TerminalProgress::default(),
+    )
+    .await;
+
+    finish_build(result, mismatch_report_file, mismatch_report, start);
+}
+
+fn build_from_dump(
+    file: String,
+    height: u32,
+    db: DatabaseArgs,
+    mismatch_report_file: Option<String>,
+) {
+    let mut dump = match BlockDump::open(Path::new(&file)) {
+        Ok(dump) => dump,
+        Err(error) => {
+            log::error!(?error, file, "Could not open block dump");
+            std::process::exit(1);
+        }
+    };
+    let (db_path, env) = open_database(&db);
+
+    // Build history tree
+    log::info!(?db_path, file, "Building history tree from block dump");
+    let start = Instant::now();
+    let mut mismatch_report = mismatch_report_file
+        .as_ref()
+        .map(|_| HashMismatchReport::default());
+    let result = get_history_root_from_dump(
+        &mut dump,
+        height,
+        env,
+        db.network.pow_network_id(),
+        mismatch_report.as_mut(),
+        &TerminalProgress::default(),
+    );
+
+    finish_build(result, mismatch_report_file, mismatch_report, start);
+}
+
+async fn verify(source: SourceArgs) {
+    let (client, db_path, env) = setup(&source).await;
+
+    // Verify history tree
+    log::info!(?db_path, "Verifying history tree");
+    let start = Instant::now();
+    match verify_history(
+        &client,
+        source.height,
+        env,
+        source.db.network.pow_network_id(),
+        &TerminalProgress::default(),
+    )
+    .await
+    {
         Ok(history_root) => {
             let duration = start.elapsed();
             log::info!(
                 duration = humantime::format_duration(duration).to_string(),
                 history_root = history_root.to_hex(),
-                "Finished building history tree"
+                "History tree matches the PoW chain"
             )
         }
         Err(e) => {
-            log::error!(error = ?e, "Failed to build history root");
+            log::error!(error = ?e, "History tree verification failed");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn export(db: DatabaseArgs, file: String) {
+    let (db_path, env) = open_database(&db);
+
+    log::info!(?db_path, file, "Exporting history archive");
+    let start = Instant::now();
+    match export_history(env, Path::new(&file)) {
+        Ok(history_root) => {
+            let duration = start.elapsed();
+            log::info!(
+                duration = humantime::format_duration(duration).to_string(),
+                history_root = history_root.to_hex(),
+                "Finished exporting history archive"
+            )
+        }
+        Err(e) => {
+            log::error!(error = ?e, "Failed to export history archive");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn import(db: DatabaseArgs, file: String, expected_root: String) {
+    let expected_root = match Blake2bHash::from_str(&expected_root) {
+        Ok(hash) => hash,
+        Err(error) => {
+            log::error!(?error, "Invalid expected history root");
+            std::process::exit(1);
+        }
+    };
+    let (db_path, env) = open_database(&db);
+
+    log::info!(?db_path, file, "Importing history archive");
+    let start = Instant::now();
+    match import_history(env, Path::new(&file), &expected_root) {
+        Ok(history_root) => {
+            let duration = start.elapsed();
+            log::info!(
+                duration = humantime::format_duration(duration).to_string(),
+                history_root = history_root.to_hex(),
+                "Finished importing history archive"
+            )
+        }
+        Err(e) => {
+            log::error!(error = ?e, "Failed to import history archive");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn query(db: DatabaseArgs, target: QueryTarget, max: u16, prove: bool) {
+    let (_, env) = open_database(&db);
+    let query = HistoryQuery::new(env);
+
+    let tx_hash = target.tx_hash.as_ref().map(|tx_hash| {
+        Blake2bHash::from_str(tx_hash).unwrap_or_else(|error| {
+            log::error!(?error, "Invalid transaction hash");
             std::process::exit(1);
+        })
+    });
+
+    let ext_transactions = if let Some(hash) = &tx_hash {
+        query.by_hash(hash)
+    } else if let Some(address) = &target.address {
+        match Address::from_user_friendly_address(address) {
+            Ok(address) => query.by_address(&address, max),
+            Err(error) => {
+                log::error!(?error, "Invalid address");
+                std::process::exit(1);
+            }
         }
+    } else if let Some(block_number) = target.block_number {
+        query.by_block_number(block_number)
+    } else {
+        unreachable!("A query target is required");
+    };
+
+    log::info!(
+        count = ext_transactions.len(),
+        "Found migrated transactions"
+    );
+    for ext_transaction in &ext_transactions {
+        log::info!(
+            block_number = ext_transaction.block_number,
+            block_time = ext_transaction.block_time,
+            hash = ext_transaction.tx_hash().to_hex(),
+            data = ?ext_transaction.data,
+            "Migrated transaction"
+        );
+    }
+
+    // `prove` requires a transaction hash
+    if let (true, Some(hash)) = (prove, &tx_hash) {
+        let history_root = match query.history_root() {
+            Ok(history_root) => history_root,
+            Err(e) => {
+                log::error!(error = ?e, "Failed to get history root");
+                std::process::exit(1);
+            }
+        };
+        match query.prove(hash) {
+            Ok(proof) => log::info!(
+                history_root = history_root.to_hex(),
+                verified = HistoryQuery::verify_proof(&proof, &history_root),
+                proof = hex::encode(proof.serialize_to_vec()),
+                "Inclusion proof"
+            ),
+            Err(e) => {
+                log::error!(error = ?e, "Failed to build inclusion proof");
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+
+    initialize_logging();
+
+    match args.command {
+        Command::Build {
+            source,
+            mismatch_report,
+        } => build(source, mismatch_report).await,
+        Command::BuildFromDump {
+            file,
+            height,
+            db,
+            mismatch_report,
+        } => build_from_dump(file, height, db, mismatch_report),
+        Command::Verify { source } => verify(source).await,
+        Command::Export { db, file } => export(db, file),
+        Command::Query {
+            db,
+            target,
+            max,
+            prove,
+        } => query(db, target, max, prove),
+        Command::Import {
+            db,
+            file,
+            expected_root,
+        } => import(db, file, expected_root),
     }
 }
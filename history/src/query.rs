@@ -0,0 +1,66 @@
+//! Queries over the migrated history
+//!
+//! Allows to check whether PoW transactions made it into the PoS history store
+//! and to prove their inclusion against the history root.
+
+use nimiq_blockchain::{HistoryStore, HistoryTreeProof};
+use nimiq_database::DatabaseProxy;
+use nimiq_hash::Blake2bHash;
+use nimiq_keys::Address;
+use nimiq_transaction::extended_transaction::ExtendedTransaction;
+
+use crate::Error;
+
+/// Read-only access to a migrated history store
+pub struct HistoryQuery {
+    history_store: HistoryStore,
+}
+
+impl HistoryQuery {
+    /// Creates a query over the history store in `env`
+    pub fn new(env: DatabaseProxy) -> Self {
+        Self {
+            history_store: HistoryStore::new(env),
+        }
+    }
+
+    /// Gets the current history root
+    pub fn history_root(&self) -> Result<Blake2bHash, Error> {
+        self.history_store
+            .get_history_tree_root(0, None)
+            .ok_or(Error::HistoryRootError)
+    }
+
+    /// Gets the migrated transactions with the given hash
+    pub fn by_hash(&self, hash: &Blake2bHash) -> Vec<ExtendedTransaction> {
+        self.history_store.get_ext_tx_by_hash(hash, None)
+    }
+
+    /// Gets up to `max` migrated transactions sent or received by `address`
+    pub fn by_address(&self, address: &Address, max: u16) -> Vec<ExtendedTransaction> {
+        self.history_store
+            .get_tx_hashes_by_address(address, max, None)
+            .iter()
+            .flat_map(|hash| self.by_hash(hash))
+            .collect()
+    }
+
+    /// Gets the migrated transactions of a PoW block
+    pub fn by_block_number(&self, block_number: u32) -> Vec<ExtendedTransaction> {
+        self.history_store
+            .get_block_transactions(block_number, None)
+    }
+
+    /// Builds a proof of inclusion of the transaction with the given hash in
+    /// the history tree.
+    pub fn prove(&self, hash: &Blake2bHash) -> Result<HistoryTreeProof, Error> {
+        self.history_store
+            .prove(0, vec![hash], None, None)
+            .ok_or(Error::UnknownTransaction)
+    }
+
+    /// Verifies an inclusion proof against `history_root`
+    pub fn verify_proof(proof: &HistoryTreeProof, history_root: &Blake2bHash) -> bool {
+        proof.verify(history_root.clone()).unwrap_or(false)
+    }
+}
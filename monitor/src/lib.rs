@@ -1,44 +1,37 @@
+pub mod ready;
+pub mod slots;
+pub mod transaction;
 pub mod types;
-use percentage::Percentage;
-use std::{collections::HashMap, ops::Range};
+use std::ops::Range;
 
 use log::{error, info};
 use nimiq_keys::Address;
-use nimiq_primitives::coin::Coin;
-use nimiq_rpc::{
-    primitives::{OutgoingTransaction, TransactionDetails},
-    Client,
-};
+use nimiq_rpc::{primitives::TransactionDetails, Client};
 use nimiq_state_migration::types::GenesisValidator;
 
-use types::{Error, ValidatorsReadiness, ACTIVATION_HEIGHT};
-
-/// Stake percentage that is considered to indicate that the validators are ready
-pub const READY_PERCENTAGE: u8 = 80;
-
-// Sends a transaction to the Nimiq PoW chain to report that we are ready
-// The transaction format is defined as follow:
-//   Sender: Validator address
-//   Recipient: Burn address
-//   Value: 100 Lunas
-//   Data: TBD
-//
-//
-pub fn generate_ready_tx(validator: String) -> OutgoingTransaction {
-    info!(" Generating ready transaction, from {} ", validator);
-    OutgoingTransaction {
-        from: validator,
-        to: Address::burn_address().to_user_friendly_address(),
-        value: 1, //Lunas
-        fee: 0,
-    }
+use ready::ReadyMessage;
+use slots::SlotDistribution;
+use transaction::READY_TX_VALUE;
+use types::{Error, ReadinessRule, ReadySlots, ValidatorsReadiness, ACTIVATION_HEIGHT};
+
+// Checks if a transaction is a ready transaction carrying the given ready message
+fn is_ready_txn(txn: &TransactionDetails, message: &ReadyMessage) -> bool {
+    txn.to_address == Address::burn_address().to_user_friendly_address()
+        && txn.value == READY_TX_VALUE
+        && txn
+            .data
+            .as_deref()
+            .and_then(|data| ReadyMessage::from_hex(data).ok())
+            .is_some_and(|txn_message| txn_message == *message)
 }
 
 // Checks if we have seen a ready transaction from a validator in the specified range
+// that agrees on the candidate of the given ready message
 pub async fn get_ready_txns(
     client: &Client,
     validator: String,
     block_window: Range<u32>,
+    message: &ReadyMessage,
 ) -> Vec<TransactionDetails> {
     if let Ok(transactions) = client.get_transactions_by_address(&validator, 10).await {
         let filtered_txns: Vec<TransactionDetails> = transactions
@@ -47,8 +40,7 @@ pub async fn get_ready_txns(
                 // Here we filter by current epoch
                 (txn.block_number > block_window.start)
                     && (txn.block_number < block_window.end)
-                    && (txn.to_address == Address::burn_address().to_user_friendly_address())
-                    && txn.value == 1
+                    && is_ready_txn(txn, message)
             })
             .collect();
         filtered_txns
@@ -57,9 +49,9 @@ pub async fn get_ready_txns(
     }
 }
 
-// Sends a transaction into the Nimiq PoW chain
-pub async fn send_tx(client: &Client, transaction: OutgoingTransaction) -> Result<(), Error> {
-    match client.send_transaction(&transaction).await {
+// Sends a serialized transaction into the Nimiq PoW chain
+pub async fn send_tx(client: &Client, transaction: &[u8]) -> Result<(), Error> {
+    match client.send_raw_transaction(&hex::encode(transaction)).await {
         Ok(_) => {
             info!(" Sent transaction to the Nimiq PoW network");
             Ok(())
@@ -73,30 +65,27 @@ pub async fn send_tx(client: &Client, transaction: OutgoingTransaction) -> Resul
 
 // Checks if enough validators are ready
 // If thats the case, the number of slots which are ready are returned
-// The validators_allocation is a HashMap from Validator to number of slots owned by that validator
+// The slots of the first epoch are estimated from the stake of the validators
+// and the number of slots that need to be ready is given by the readiness `rule`
+// Only ready transactions that agree on the candidate of the given ready message are counted
 pub async fn check_validators_ready(
     client: &Client,
     validators: Vec<GenesisValidator>,
+    rule: ReadinessRule,
+    message: &ReadyMessage,
 ) -> ValidatorsReadiness {
-    // First calculate the total amount of stake
-    let total_stake: Coin = validators.iter().map(|validator| validator.balance).sum();
-
-    log::debug!(" The total registered stake is {}", total_stake);
-
-    // First we need to obtain the validator list, along with the slot allocation for the first epoch.
-    let mut validator_list = HashMap::new();
-
-    // This is a mock list for testing purposes(for now)
-    // The validator address and the slots assigned to each address
-    validator_list.insert(
-        "NQ28 GSPY V07Q DJTK Y8TG DFYD KR5Q 9KBF HV5A".to_string(),
-        100u16,
-    );
-
-    validator_list.insert(
-        "NQ56 7L0M GQPS GNCU VGGT LV4S 4HHN F701 2DEF".to_string(),
-        412u16,
-    );
+    // First we need to estimate the slot allocation for the first epoch.
+    let slot_distribution = SlotDistribution::estimate(&validators);
+    let total_slots = slot_distribution.total_slots();
+
+    log::debug!(" The total number of slots is {}", total_slots);
+    for (address, slots) in slot_distribution.iter() {
+        log::debug!(
+            " Validator {} has {} slots",
+            address.to_user_friendly_address(),
+            slots
+        );
+    }
 
     let mut ready_validators = Vec::new();
 
@@ -118,10 +107,8 @@ pub async fn check_validators_ready(
             let filtered_txns: Vec<TransactionDetails> = transactions
                 .into_iter()
                 .filter(|txn| {
-                    // Here we filter by the readiness criteria, TBD
-                    (txn.block_number > ACTIVATION_HEIGHT)
-                        && (txn.to_address == Address::burn_address().to_user_friendly_address())
-                        && txn.value == 1
+                    // Here we filter by the readiness criteria
+                    (txn.block_number > ACTIVATION_HEIGHT) && is_ready_txn(txn, message)
                 })
                 .collect();
             info!(
@@ -134,37 +121,45 @@ pub async fn check_validators_ready(
         }
     }
 
-    // Now we need to see if we have enough stake ready
-    let mut ready_stake = Coin::ZERO;
+    // Now we need to see if we have enough slots ready
+    let mut ready_slots = 0u16;
 
     for ready_validator in ready_validators {
-        ready_stake += ready_validator.balance;
+        let slots = slot_distribution.slots_of(&ready_validator.validator.validator_address);
+        ready_slots += slots;
 
         info!(
-            " Validator {} is ready with {} stake.",
+            " Validator {} is ready with {} slots.",
             ready_validator
                 .validator
                 .validator_address
                 .to_user_friendly_address(),
-            ready_validator.balance
+            slots
         );
     }
 
-    info!(" We have {} total stake ready", u64::from(ready_stake));
-    let percent = Percentage::from(READY_PERCENTAGE);
+    info!(" We have {} total slots ready", ready_slots);
 
-    let needed_stake = percent.apply_to(u64::from(total_stake));
+    let needed_slots = rule.threshold(total_slots);
 
-    info!(" We need at least {} stake to be ready", needed_stake);
+    info!(
+        " We need at least {} of {} slots to be ready ({})",
+        needed_slots, total_slots, rule
+    );
 
-    if u64::from(ready_stake) >= needed_stake {
+    let ready_slots = ReadySlots {
+        ready: ready_slots,
+        total: total_slots,
+        threshold: needed_slots,
+    };
+    if total_slots > 0 && ready_slots.ready >= needed_slots {
         info!(" Enough validators are ready to start the PoS Chain! ");
-        ValidatorsReadiness::Ready(ready_stake)
+        ValidatorsReadiness::Ready(ready_slots)
     } else {
         info!(
-            " Not enough validators are ready, we need at least {} stake ",
-            needed_stake
+            " Not enough validators are ready, we need at least {} slots ",
+            needed_slots
         );
-        ValidatorsReadiness::NotReady(ready_stake)
+        ValidatorsReadiness::NotReady(ready_slots)
     }
 }
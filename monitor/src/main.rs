@@ -1,14 +1,18 @@
-use std::{process::exit, thread::sleep, time::Duration};
+use std::{path::Path, process::exit, thread::sleep, time::Duration};
 
 use clap::Parser;
-use log::info;
+use log::{error, info};
+use nimiq_keys::Address;
 use nimiq_pow_monitor::{
-    check_validators_ready, generate_ready_tx, get_ready_txns, send_tx,
-    types::{ValidatorsReadiness, ACTIVATION_HEIGHT},
+    check_validators_ready, get_ready_txns,
+    ready::ReadyMessage,
+    send_tx,
+    transaction::{build_ready_tx, read_key_file, DEFAULT_FEE_PER_BYTE},
+    types::{ReadinessRule, ValidatorsReadiness, ACTIVATION_HEIGHT},
 };
-use nimiq_primitives::policy::Policy;
+use nimiq_primitives::{networks::NetworkId, policy::Policy};
 use nimiq_rpc::Client;
-use nimiq_state_migration::types::GenesisValidator;
+use nimiq_state_migration::{get_stakers, get_validators};
 use simple_logger::SimpleLogger;
 use url::Url;
 
@@ -20,9 +24,69 @@ struct Args {
     #[arg(short, long)]
     rpc: String,
 
-    /// The validator address
+    /// File with the hex encoded private key of the validator address, used to
+    /// sign the ready transaction
     #[arg(short, long)]
-    validator: String,
+    key_file: String,
+
+    /// PoW network the ready transaction is sent to
+    #[arg(short, long, value_enum)]
+    network: PoWNetwork,
+
+    /// Fee per byte of the ready transaction in Lunas
+    #[arg(long, default_value_t = DEFAULT_FEE_PER_BYTE)]
+    fee_per_byte: u64,
+
+    /// Rule deciding whether enough validators are ready: `2f+1` of the
+    /// slots or a percentage of the slots such as `80%`
+    #[arg(long, default_value_t = ReadinessRule::TwoFPlusOne)]
+    readiness_rule: ReadinessRule,
+
+    /// First block of the validator registration window
+    #[arg(long)]
+    registration_start: u32,
+
+    /// Block after the validator registration window
+    #[arg(long)]
+    registration_end: u32,
+
+    /// First block of the pre-stake registration window
+    #[arg(long)]
+    pre_stake_start: u32,
+
+    /// Block after the pre-stake registration window
+    #[arg(long)]
+    pre_stake_end: u32,
+
+    /// Optional hex encoded expected PoS genesis hash or SHA-256 of the genesis
+    /// manifest to include in the ready message
+    #[arg(long, value_parser = parse_candidate_hash)]
+    candidate_hash: Option<[u8; 32]>,
+}
+
+/// PoW networks
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum PoWNetwork {
+    Main,
+    Test,
+    Dev,
+}
+
+impl From<PoWNetwork> for NetworkId {
+    fn from(network: PoWNetwork) -> Self {
+        match network {
+            PoWNetwork::Main => NetworkId::Main,
+            PoWNetwork::Test => NetworkId::Test,
+            PoWNetwork::Dev => NetworkId::Dev,
+        }
+    }
+}
+
+fn parse_candidate_hash(hash: &str) -> Result<[u8; 32], String> {
+    hex::decode(hash)
+        .map_err(|error| error.to_string())?
+        .try_into()
+        .map_err(|_| "Expected a 32 byte hash".to_string())
 }
 
 #[tokio::main]
@@ -33,7 +97,14 @@ async fn main() {
         .unwrap();
 
     let args = Args::parse();
-    let validator_address = args.validator.clone();
+    let key_pair = match read_key_file(Path::new(&args.key_file)) {
+        Ok(key_pair) => key_pair,
+        Err(err) => {
+            error!(" Could not read key file {}, error: {}", args.key_file, err);
+            exit(1);
+        }
+    };
+    let validator_address = Address::from(&key_pair.public).to_user_friendly_address();
 
     info!(" This is our validator address: {}", validator_address);
 
@@ -54,8 +125,36 @@ async fn main() {
         sleep(Duration::from_secs(10));
     }
 
+    // The slots of the first epoch are estimated from the registered validators
+    // and the stake delegated to them
+    let registered_validators =
+        match get_validators(&client, args.registration_start..args.registration_end).await {
+            Ok(validators) => validators,
+            Err(err) => {
+                error!(
+                    " Error obtaining the list of registered validators: {}",
+                    err
+                );
+                exit(1);
+            }
+        };
+    let validators = match get_stakers(
+        &client,
+        &registered_validators,
+        args.pre_stake_start..args.pre_stake_end,
+    )
+    .await
+    {
+        Ok((_, validators)) => validators,
+        Err(err) => {
+            error!(" Error obtaining the list of stakers: {}", err);
+            exit(1);
+        }
+    };
+    info!(" There are {} registered validators", validators.len());
+
     let mut reported_ready = false;
-    loop {
+    let candidate = loop {
         let current_height = client.block_number().await.unwrap();
         info!(" Current block height: {}", current_height);
 
@@ -66,20 +165,34 @@ async fn main() {
             previous_election_block = ACTIVATION_HEIGHT;
         }
 
+        // Ready messages target the next election block as candidate
+        let ready_message = ReadyMessage::new(next_election_block, args.candidate_hash);
+
         if !reported_ready {
             // Obtain all the transactions that we have sent previously.
             let transactions = get_ready_txns(
                 &client,
                 validator_address.clone(),
                 previous_election_block..next_election_block,
+                &ready_message,
             )
             .await;
 
             if transactions.is_empty() {
                 // Report we are ready to the Nimiq PoW chain:
-                let transaction = generate_ready_tx(validator_address.clone());
+                info!(
+                    " Generating ready transaction, from {} for election block {}",
+                    validator_address, ready_message.election_block
+                );
+                let transaction = build_ready_tx(
+                    &key_pair,
+                    &ready_message,
+                    current_height,
+                    args.network.into(),
+                    args.fee_per_byte,
+                );
 
-                match send_tx(&client, transaction).await {
+                match send_tx(&client, &transaction).await {
                     Ok(_) => reported_ready = true,
                     Err(_) => exit(1),
                 }
@@ -88,21 +201,26 @@ async fn main() {
                 reported_ready = true;
             }
         }
-        let validator_list: Vec<GenesisValidator> = Vec::new();
-        let validators_status = check_validators_ready(&client, validator_list).await;
+        let validators_status = check_validators_ready(
+            &client,
+            validators.clone(),
+            args.readiness_rule,
+            &ready_message,
+        )
+        .await;
         match validators_status {
             ValidatorsReadiness::NotReady(slots) => {
                 info!(
-                    "Not enough validators are ready yet, we have {} slots ready",
-                    slots
+                    "Not enough validators are ready yet, we have {} of {} slots ready and need {}",
+                    slots.ready, slots.total, slots.threshold
                 );
             }
             ValidatorsReadiness::Ready(slots) => {
                 info!(
-                    "Enough validators are ready to start the PoS chain, we have {} slots ready",
-                    slots
+                    "Enough validators are ready to start the PoS chain, we have {} of {} slots ready and need {}",
+                    slots.ready, slots.total, slots.threshold
                 );
-                break;
+                break ready_message.election_block;
             }
         }
 
@@ -112,12 +230,9 @@ async fn main() {
         {
             reported_ready = false;
         }
-    }
-
-    // Now that we have enough validators ready, we need to pick the next election block candidate
-
-    let candidate = Policy::election_block_after(client.block_number().await.unwrap());
+    };
 
+    // The election block candidate is the one the ready validators agreed on
     info!("The next election candidate is {}", candidate);
 
     loop {
@@ -0,0 +1,130 @@
+use crate::types::Error;
+
+/// Version of the ready message format
+pub const READY_MESSAGE_VERSION: u8 = 1;
+
+/// Size of the optional candidate hash of a ready message
+const CANDIDATE_HASH_SIZE: usize = 32;
+
+/// Payload of a ready transaction, stating which PoW state the validator
+/// intends to migrate
+///
+/// The message is encoded in the transaction data as follows:
+///   Format version: 1 byte (`READY_MESSAGE_VERSION`)
+///   Target election block height: 4 bytes, big endian
+///   Candidate hash: 32 bytes, optional
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReadyMessage {
+    /// Height of the election block that is the candidate final PoW block
+    pub election_block: u32,
+    /// Optional expected PoS genesis hash or SHA-256 of the genesis manifest
+    pub candidate_hash: Option<[u8; CANDIDATE_HASH_SIZE]>,
+}
+
+impl ReadyMessage {
+    /// Creates a ready message for the given election block candidate
+    pub fn new(election_block: u32, candidate_hash: Option<[u8; CANDIDATE_HASH_SIZE]>) -> Self {
+        ReadyMessage {
+            election_block,
+            candidate_hash,
+        }
+    }
+
+    /// Serializes the message into transaction data
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![READY_MESSAGE_VERSION];
+        bytes.extend_from_slice(&self.election_block.to_be_bytes());
+        if let Some(candidate_hash) = &self.candidate_hash {
+            bytes.extend_from_slice(candidate_hash);
+        }
+        bytes
+    }
+
+    /// Parses a message from transaction data
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.first() != Some(&READY_MESSAGE_VERSION) {
+            return Err(Error::InvalidReadyMessage);
+        }
+        let election_block = u32::from_be_bytes(
+            bytes
+                .get(1..5)
+                .ok_or(Error::InvalidReadyMessage)?
+                .try_into()
+                .map_err(|_| Error::InvalidReadyMessage)?,
+        );
+        let candidate_hash = match &bytes[5..] {
+            [] => None,
+            hash => Some(hash.try_into().map_err(|_| Error::InvalidReadyMessage)?),
+        };
+        Ok(ReadyMessage {
+            election_block,
+            candidate_hash,
+        })
+    }
+
+    /// Serializes the message into hex encoded transaction data
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.to_bytes())
+    }
+
+    /// Parses a message from hex encoded transaction data
+    pub fn from_hex(data: &str) -> Result<Self, Error> {
+        Self::from_bytes(&hex::decode(data).map_err(|_| Error::InvalidReadyMessage)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_without_candidate_hash() {
+        let message = ReadyMessage::new(1_000_000, None);
+        let bytes = message.to_bytes();
+        assert_eq!(bytes, [READY_MESSAGE_VERSION, 0x00, 0x0f, 0x42, 0x40]);
+        assert_eq!(ReadyMessage::from_bytes(&bytes).unwrap(), message);
+        assert_eq!(ReadyMessage::from_hex(&message.to_hex()).unwrap(), message);
+    }
+
+    #[test]
+    fn round_trip_with_candidate_hash() {
+        let message = ReadyMessage::new(1_000_000, Some([0x5a; CANDIDATE_HASH_SIZE]));
+        let bytes = message.to_bytes();
+        assert_eq!(bytes.len(), 5 + CANDIDATE_HASH_SIZE);
+        assert_eq!(&bytes[5..], &[0x5a; CANDIDATE_HASH_SIZE]);
+        assert_eq!(ReadyMessage::from_bytes(&bytes).unwrap(), message);
+        assert_eq!(ReadyMessage::from_hex(&message.to_hex()).unwrap(), message);
+    }
+
+    #[test]
+    fn rejects_wrong_version() {
+        let mut bytes = ReadyMessage::new(1_000_000, None).to_bytes();
+        bytes[0] = READY_MESSAGE_VERSION + 1;
+        assert!(ReadyMessage::from_bytes(&bytes).is_err());
+        assert!(ReadyMessage::from_bytes(&[]).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_payload() {
+        let bytes = ReadyMessage::new(1_000_000, Some([0x5a; CANDIDATE_HASH_SIZE])).to_bytes();
+        for len in 1..bytes.len() {
+            if len == 5 {
+                // A payload without candidate hash is valid
+                continue;
+            }
+            assert!(ReadyMessage::from_bytes(&bytes[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_over_long_payload() {
+        let mut bytes = ReadyMessage::new(1_000_000, Some([0x5a; CANDIDATE_HASH_SIZE])).to_bytes();
+        bytes.push(0);
+        assert!(ReadyMessage::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_hex() {
+        assert!(ReadyMessage::from_hex("not hex").is_err());
+    }
+}
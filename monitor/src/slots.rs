@@ -0,0 +1,135 @@
+use std::collections::BTreeMap;
+
+use nimiq_keys::Address;
+use nimiq_primitives::policy::Policy;
+use nimiq_state_migration::types::GenesisValidator;
+
+/// Estimated slots of each validator in the first epoch of the PoS chain
+#[derive(Clone, Debug, Default)]
+pub struct SlotDistribution {
+    slots: BTreeMap<Address, u16>,
+}
+
+impl SlotDistribution {
+    /// Estimates the slots of the first epoch from the stake of the validators.
+    ///
+    /// The actual slots are sampled proportionally to the stake using the VRF
+    /// seed of the PoS genesis block, which is not known before the genesis is
+    /// built. Instead, each validator gets its expected share of the
+    /// `Policy::SLOTS` slots, rounded with the largest remainder method. Equal
+    /// remainders are broken by address, so the estimate is deterministic.
+    ///
+    /// Validators without stake get no slots.
+    pub fn estimate(validators: &[GenesisValidator]) -> Self {
+        Self::from_stakes(
+            validators
+                .iter()
+                .filter(|validator| !validator.balance.is_zero())
+                .map(|validator| {
+                    (
+                        validator.validator.validator_address.clone(),
+                        u64::from(validator.balance),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    fn from_stakes(stakes: BTreeMap<Address, u64>) -> Self {
+        let total_stake: u128 = stakes.values().map(|stake| *stake as u128).sum();
+        if total_stake == 0 {
+            return SlotDistribution::default();
+        }
+
+        let mut slots = BTreeMap::new();
+        let mut remainders = Vec::with_capacity(stakes.len());
+        let mut assigned = 0u16;
+        for (address, stake) in stakes {
+            let quota = stake as u128 * Policy::SLOTS as u128;
+            let validator_slots = (quota / total_stake) as u16;
+            assigned += validator_slots;
+            remainders.push((quota % total_stake, address.clone()));
+            slots.insert(address, validator_slots);
+        }
+
+        // Largest remainders first, ties in address order
+        remainders.sort_by(|(a, a_address), (b, b_address)| {
+            b.cmp(a).then_with(|| a_address.cmp(b_address))
+        });
+        for (_, address) in remainders
+            .into_iter()
+            .take((Policy::SLOTS - assigned) as usize)
+        {
+            *slots.entry(address).or_insert(0) += 1;
+        }
+        slots.retain(|_, slots| *slots > 0);
+        SlotDistribution { slots }
+    }
+
+    /// Gets the number of slots of a validator
+    pub fn slots_of(&self, validator_address: &Address) -> u16 {
+        self.slots.get(validator_address).copied().unwrap_or(0)
+    }
+
+    /// Gets the total number of slots assigned
+    pub fn total_slots(&self) -> u16 {
+        self.slots.values().sum()
+    }
+
+    /// Iterates over the validators with slots and their number of slots
+    pub fn iter(&self) -> impl Iterator<Item = (&Address, u16)> {
+        self.slots.iter().map(|(address, slots)| (address, *slots))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(byte: u8) -> Address {
+        Address::from([byte; Address::SIZE])
+    }
+
+    #[test]
+    fn all_slots_are_assigned_proportionally() {
+        let distribution = SlotDistribution::from_stakes(BTreeMap::from([
+            (address(1), 1),
+            (address(2), 1),
+            (address(3), 2),
+        ]));
+        assert_eq!(distribution.total_slots(), Policy::SLOTS);
+        assert!(distribution.slots_of(&address(3)) >= Policy::SLOTS / 2);
+        assert!(distribution.slots_of(&address(1)) >= Policy::SLOTS / 4);
+        assert!(distribution.slots_of(&address(2)) >= Policy::SLOTS / 4);
+    }
+
+    #[test]
+    fn equal_remainders_are_broken_by_address() {
+        let stakes: BTreeMap<Address, u64> = (1..=3).map(|byte| (address(byte), 1)).collect();
+        let distribution = SlotDistribution::from_stakes(stakes.clone());
+        assert_eq!(distribution.total_slots(), Policy::SLOTS);
+
+        let base = Policy::SLOTS / 3;
+        let extra = Policy::SLOTS % 3;
+        for (index, byte) in (1..=3).enumerate() {
+            let expected = base + u16::from((index as u16) < extra);
+            assert_eq!(distribution.slots_of(&address(byte)), expected);
+        }
+        assert_eq!(
+            SlotDistribution::from_stakes(stakes).slots,
+            distribution.slots
+        );
+    }
+
+    #[test]
+    fn no_stake_gets_no_slots() {
+        assert_eq!(
+            SlotDistribution::from_stakes(BTreeMap::new()).total_slots(),
+            0
+        );
+        assert_eq!(
+            SlotDistribution::from_stakes(BTreeMap::from([(address(1), 0)])).total_slots(),
+            0
+        );
+    }
+}
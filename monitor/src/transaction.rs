@@ -0,0 +1,163 @@
+use std::{fs, path::Path};
+
+use nimiq_keys::{Address, KeyPair, PrivateKey};
+use nimiq_primitives::networks::NetworkId;
+
+use crate::{ready::ReadyMessage, types::Error};
+
+/// Value of a ready transaction in Lunas
+pub const READY_TX_VALUE: u64 = 1;
+
+/// Default fee per byte of a ready transaction in Lunas. PoW nodes limit the
+/// number of free transactions per sender, so ready transactions pay the
+/// usual minimum fee per byte.
+pub const DEFAULT_FEE_PER_BYTE: u64 = 1;
+
+/// Format byte of PoW extended transactions
+const EXTENDED_TX_FORMAT: u8 = 1;
+
+/// Account type of PoW basic accounts
+const BASIC_ACCOUNT_TYPE: u8 = 0;
+
+/// Size of an extended transaction without its data: format, data length,
+/// sender and recipient with their types, value, fee, validity start height,
+/// network ID, flags, proof length and a signature proof of a basic account
+/// (public key, empty Merkle path and signature)
+const EXTENDED_TX_SIZE_WITHOUT_DATA: usize =
+    1 + 2 + 20 + 1 + 20 + 1 + 8 + 8 + 4 + 1 + 1 + 2 + SIGNATURE_PROOF_SIZE;
+
+/// Size of the signature proof of a basic account
+const SIGNATURE_PROOF_SIZE: usize = 32 + 1 + 64;
+
+/// Reads a key pair from a file with a hex encoded Ed25519 private key
+pub fn read_key_file(path: &Path) -> Result<KeyPair, Error> {
+    let key = hex::decode(fs::read_to_string(path)?.trim()).map_err(|_| Error::InvalidKey)?;
+    let private_key = PrivateKey::from_bytes(&key).map_err(|_| Error::InvalidKey)?;
+    Ok(KeyPair::from(private_key))
+}
+
+/// Builds a ready transaction to report to the Nimiq PoW chain that we are
+/// ready, serialized in the PoW extended transaction format and signed with
+/// `key_pair`
+///
+/// The transaction format is defined as follows:
+///   Sender: Validator address, which is the basic account of `key_pair`
+///   Recipient: Burn address
+///   Value: `READY_TX_VALUE` Lunas
+///   Fee: `fee_per_byte` Lunas per byte of the serialized transaction
+///   Data: The ready message, see `ReadyMessage`
+///
+/// The transaction is valid from `validity_start_height` on.
+pub fn build_ready_tx(
+    key_pair: &KeyPair,
+    message: &ReadyMessage,
+    validity_start_height: u32,
+    network_id: NetworkId,
+    fee_per_byte: u64,
+) -> Vec<u8> {
+    let data = message.to_bytes();
+    let fee = (EXTENDED_TX_SIZE_WITHOUT_DATA + data.len()) as u64 * fee_per_byte;
+
+    // Content of the transaction, which is what gets signed
+    let mut content = Vec::new();
+    content.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    content.extend_from_slice(&data);
+    content.extend_from_slice(Address::from(&key_pair.public).as_slice());
+    content.push(BASIC_ACCOUNT_TYPE);
+    content.extend_from_slice(Address::burn_address().as_slice());
+    content.push(BASIC_ACCOUNT_TYPE);
+    content.extend_from_slice(&READY_TX_VALUE.to_be_bytes());
+    content.extend_from_slice(&fee.to_be_bytes());
+    content.extend_from_slice(&validity_start_height.to_be_bytes());
+    content.push(network_id as u8);
+    // Flags
+    content.push(0);
+
+    // Signature proof: public key, empty Merkle path and signature
+    let signature = key_pair.sign(&content);
+    let mut proof = Vec::with_capacity(SIGNATURE_PROOF_SIZE);
+    proof.extend_from_slice(key_pair.public.as_bytes());
+    proof.push(0);
+    proof.extend_from_slice(&signature.to_bytes());
+
+    let mut transaction = Vec::with_capacity(EXTENDED_TX_SIZE_WITHOUT_DATA + data.len());
+    transaction.push(EXTENDED_TX_FORMAT);
+    transaction.extend_from_slice(&content);
+    transaction.extend_from_slice(&(proof.len() as u16).to_be_bytes());
+    transaction.extend_from_slice(&proof);
+    transaction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Private key of the first test vector of RFC 8032
+    const PRIVATE_KEY: &str = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+
+    fn key_pair() -> KeyPair {
+        KeyPair::from(PrivateKey::from_bytes(&hex::decode(PRIVATE_KEY).unwrap()).unwrap())
+    }
+
+    // The expected transactions follow the serialization of extended
+    // transactions of the PoW client: format, data, sender, recipient, value,
+    // fee, validity start height, network ID, flags and signature proof.
+    #[test]
+    fn ready_tx_matches_reference_serialization() {
+        let message = ReadyMessage::new(2000, None);
+        let transaction = build_ready_tx(&key_pair(), &message, 1990, NetworkId::Test, 1);
+
+        let expected = hex::decode(concat!(
+            "01",
+            "0005",
+            "01000007d0",
+            "7849ac3049680be1ef762efe0d36e01733c3464e",
+            "00",
+            "0000000000000000000000000000000000000000",
+            "00",
+            "0000000000000001",
+            "00000000000000ab",
+            "000007c6",
+            "01",
+            "00",
+            "0061",
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+            "00",
+            "2aea2cdb496b7e4d6c3ca0b3dc35f441e2bc7547fe5995ac24b39d18a2de38e7",
+            "2646a5b92d587c6ef77a6042b9ed3830c361b5f2c0430ab058aa77b2e6d26a09",
+        ))
+        .unwrap();
+        assert_eq!(transaction, expected);
+        // The fee is one Luna per byte of the transaction
+        assert_eq!(transaction.len(), 0xab);
+    }
+
+    #[test]
+    fn ready_tx_with_candidate_hash_matches_reference_serialization() {
+        let message = ReadyMessage::new(2000, Some([0xab; 32]));
+        let transaction = build_ready_tx(&key_pair(), &message, 1990, NetworkId::Test, 0);
+
+        let expected = hex::decode(concat!(
+            "01",
+            "0025",
+            "01000007d0",
+            "abababababababababababababababababababababababababababababababab",
+            "7849ac3049680be1ef762efe0d36e01733c3464e",
+            "00",
+            "0000000000000000000000000000000000000000",
+            "00",
+            "0000000000000001",
+            "0000000000000000",
+            "000007c6",
+            "01",
+            "00",
+            "0061",
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+            "00",
+            "ba2f6f54b3a09ef92e5abcd211d99e6f5e31a7ff0bed15247093e4a14e5de49c",
+            "645a63f8e2308a8112161fab2af86a3586f75f6e6a03d7d2eea5a3c7d39dad0d",
+        ))
+        .unwrap();
+        assert_eq!(transaction, expected);
+    }
+}
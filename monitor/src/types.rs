@@ -1,10 +1,80 @@
-use nimiq_primitives::coin::Coin;
+use std::{fmt, str::FromStr};
+
+use nimiq_primitives::policy::Policy;
+use percentage::Percentage;
 use thiserror::Error;
 
 pub const ACTIVATION_HEIGHT: u32 = 100;
+
+/// Rule that decides whether enough validators are ready
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReadinessRule {
+    /// The ready validators need to hold at least 2f+1 of the slots
+    /// (`Policy::TWO_F_PLUS_ONE`), which is the BFT threshold of the PoS chain
+    #[default]
+    TwoFPlusOne,
+    /// The ready validators need to hold at least this percentage of the slots
+    SlotPercentage(u8),
+}
+
+impl ReadinessRule {
+    /// Gets the number of slots that need to be ready out of `total_slots`
+    pub fn threshold(&self, total_slots: u16) -> u16 {
+        match self {
+            ReadinessRule::TwoFPlusOne => {
+                if total_slots == Policy::SLOTS {
+                    Policy::TWO_F_PLUS_ONE
+                } else {
+                    total_slots * 2 / 3 + 1
+                }
+            }
+            ReadinessRule::SlotPercentage(percent) => {
+                Percentage::from(*percent).apply_to(total_slots)
+            }
+        }
+    }
+}
+
+/// Parses a readiness rule, either `2f+1` or a slot percentage such as `80%`
+impl FromStr for ReadinessRule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "2f+1" {
+            return Ok(ReadinessRule::TwoFPlusOne);
+        }
+        s.strip_suffix('%')
+            .and_then(|percent| percent.parse().ok())
+            .filter(|percent| *percent <= 100)
+            .map(ReadinessRule::SlotPercentage)
+            .ok_or_else(|| Error::InvalidReadinessRule(s.to_string()))
+    }
+}
+
+impl fmt::Display for ReadinessRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadinessRule::TwoFPlusOne => write!(f, "2f+1"),
+            ReadinessRule::SlotPercentage(percent) => write!(f, "{}%", percent),
+        }
+    }
+}
+
+/// Estimated first epoch slots of the validators that are ready
+#[derive(Clone, Copy, Debug)]
+pub struct ReadySlots {
+    /// Slots of the validators that are ready
+    pub ready: u16,
+    /// Total number of slots
+    pub total: u16,
+    /// Number of slots that need to be ready according to the readiness rule
+    pub threshold: u16,
+}
+
+/// Readiness of the validators
 pub enum ValidatorsReadiness {
-    NotReady(Coin),
-    Ready(Coin),
+    NotReady(ReadySlots),
+    Ready(ReadySlots),
 }
 
 #[derive(Error, Debug)]
@@ -12,4 +82,16 @@ pub enum Error {
     /// RPC error
     #[error("RPC error")]
     Rpc,
+    /// Invalid readiness rule
+    #[error("Invalid readiness rule: {0}")]
+    InvalidReadinessRule(String),
+    /// Invalid ready message
+    #[error("Invalid ready message")]
+    InvalidReadyMessage,
+    /// IO error
+    #[error("I/O error: {0}")]
+    IO(#[from] std::io::Error),
+    /// Invalid private key
+    #[error("Invalid private key")]
+    InvalidKey,
 }
@@ -0,0 +1,254 @@
+//! Progress reporting for the migration builders
+//!
+//! Long running builders (history, accounts state) report their progress as
+//! `Progress` events to a `ProgressSubscriber`. This crate is shared by the
+//! history and state builders and provides a terminal progress bar
+//! (`TerminalProgress`) and a structured log subscriber (`LogProgress`);
+//! several subscribers can be combined with `ProgressSubscribers`.
+
+use std::{
+    fmt::Write,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use indicatif::{HumanDuration, ProgressBar, ProgressState, ProgressStyle};
+
+/// Phase of the migration that is being reported
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Building the history store from the PoW chain
+    History,
+    /// Verifying a history store against the PoW chain
+    HistoryVerification,
+    /// Migrating the PoW accounts
+    Accounts,
+}
+
+/// Progress event of a migration phase
+#[derive(Clone, Debug)]
+pub struct Progress {
+    /// Phase being reported
+    pub phase: Phase,
+    /// Position within the phase: the block number for the history phases and
+    /// the number of accounts for the accounts phase.
+    pub position: u64,
+    /// Final position of the phase, if known
+    pub total: Option<u64>,
+    /// Number of blocks or accounts processed since the phase started
+    pub processed: u64,
+    /// Number of transactions processed since the phase started
+    pub transactions: u64,
+    /// Time elapsed since the phase started
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// Blocks or accounts processed per second
+    pub fn throughput(&self) -> f64 {
+        let elapsed = self.elapsed.as_secs_f64();
+        if elapsed > 0.0 {
+            self.processed as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+
+    /// Estimated time until the phase is finished, if its total is known
+    pub fn eta(&self) -> Option<Duration> {
+        let total = self.total?;
+        let throughput = self.throughput();
+        if throughput <= 0.0 {
+            return None;
+        }
+        let remaining = total.saturating_sub(self.position) as f64;
+        Some(Duration::from_secs_f64(remaining / throughput))
+    }
+}
+
+/// Receiver of the progress events of the migration builders
+pub trait ProgressSubscriber: Send + Sync {
+    /// Called whenever there is progress in a phase
+    fn on_progress(&self, progress: &Progress);
+
+    /// Called once a phase is finished
+    fn on_finish(&self, progress: &Progress) {
+        self.on_progress(progress);
+    }
+}
+
+/// Subscriber that ignores every progress event
+pub struct NoProgress;
+
+impl ProgressSubscriber for NoProgress {
+    fn on_progress(&self, _progress: &Progress) {}
+}
+
+/// Forwards the progress events to several subscribers
+#[derive(Default)]
+pub struct ProgressSubscribers(pub Vec<Box<dyn ProgressSubscriber>>);
+
+impl ProgressSubscriber for ProgressSubscribers {
+    fn on_progress(&self, progress: &Progress) {
+        for subscriber in &self.0 {
+            subscriber.on_progress(progress);
+        }
+    }
+
+    fn on_finish(&self, progress: &Progress) {
+        for subscriber in &self.0 {
+            subscriber.on_finish(progress);
+        }
+    }
+}
+
+/// Draws a progress bar on the terminal for each phase
+#[derive(Default)]
+pub struct TerminalProgress {
+    bar: Mutex<Option<(Phase, ProgressBar)>>,
+}
+
+impl TerminalProgress {
+    fn new_bar(progress: &Progress) -> ProgressBar {
+        let (bar, template) = match progress.total {
+            Some(total) => (
+                ProgressBar::new(total),
+                "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {msg}: {pos}, {percent}% (~{eta} remaining)",
+            ),
+            None => (
+                ProgressBar::new_spinner(),
+                "{spinner:.green} [{elapsed_precise}] {msg}: {pos}",
+            ),
+        };
+        let style = ProgressStyle::with_template(template)
+            .unwrap_or_else(|_| ProgressStyle::default_bar())
+            .with_key("eta", |state: &ProgressState, w: &mut dyn Write| {
+                let _ = write!(w, "{}", HumanDuration(state.eta()));
+            })
+            .progress_chars("#>-");
+        bar.set_style(style);
+        bar.set_message(match progress.phase {
+            Phase::History | Phase::HistoryVerification => "Block",
+            Phase::Accounts => "Accounts",
+        });
+        bar
+    }
+}
+
+impl ProgressSubscriber for TerminalProgress {
+    fn on_progress(&self, progress: &Progress) {
+        let mut bar = self.bar.lock().unwrap_or_else(PoisonError::into_inner);
+        if !matches!(&*bar, Some((phase, _)) if *phase == progress.phase) {
+            if let Some((_, previous_bar)) = bar.take() {
+                previous_bar.abandon();
+            }
+            *bar = Some((progress.phase, Self::new_bar(progress)));
+        }
+        if let Some((_, bar)) = &*bar {
+            bar.set_position(progress.position);
+        }
+    }
+
+    fn on_finish(&self, progress: &Progress) {
+        self.on_progress(progress);
+        if let Some((_, bar)) = self
+            .bar
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+        {
+            bar.finish();
+        }
+    }
+}
+
+/// Logs the progress events at a fixed interval
+pub struct LogProgress {
+    interval: Duration,
+    last_log: Mutex<Option<Instant>>,
+}
+
+impl LogProgress {
+    /// Creates a subscriber that logs at most once every `interval`
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_log: Mutex::new(None),
+        }
+    }
+
+    fn log(progress: &Progress, message: &str) {
+        log::info!(
+            phase = ?progress.phase,
+            position = progress.position,
+            total = ?progress.total,
+            transactions = progress.transactions,
+            throughput = format!("{:.2}/s", progress.throughput()),
+            eta = ?progress
+                .eta()
+                .map(|eta| humantime::format_duration(Duration::from_secs(eta.as_secs())).to_string()),
+            "{}",
+            message
+        );
+    }
+}
+
+impl ProgressSubscriber for LogProgress {
+    fn on_progress(&self, progress: &Progress) {
+        let mut last_log = self.last_log.lock().unwrap_or_else(PoisonError::into_inner);
+        if last_log.map_or(true, |last_log| last_log.elapsed() >= self.interval) {
+            *last_log = Some(Instant::now());
+            Self::log(progress, "Migration progress");
+        }
+    }
+
+    fn on_finish(&self, progress: &Progress) {
+        Self::log(progress, "Migration phase finished");
+    }
+}
+
+/// Keeps track of the progress of a phase and reports it to a subscriber
+pub struct ProgressTracker<'a> {
+    subscriber: &'a dyn ProgressSubscriber,
+    progress: Progress,
+    start: Instant,
+}
+
+impl<'a> ProgressTracker<'a> {
+    /// Starts tracking a phase
+    pub fn new(subscriber: &'a dyn ProgressSubscriber, phase: Phase, total: Option<u64>) -> Self {
+        Self {
+            subscriber,
+            progress: Progress {
+                phase,
+                position: 0,
+                total,
+                processed: 0,
+                transactions: 0,
+                elapsed: Duration::ZERO,
+            },
+            start: Instant::now(),
+        }
+    }
+
+    /// Sets the position without counting it as processed, e.g. when resuming
+    pub fn skip_to(&mut self, position: u64) {
+        self.progress.position = position;
+    }
+
+    /// Advances the position by `items` processed items and `transactions`
+    /// processed transactions
+    pub fn advance(&mut self, items: u64, transactions: u64) {
+        self.progress.position += items;
+        self.progress.processed += items;
+        self.progress.transactions += transactions;
+        self.progress.elapsed = self.start.elapsed();
+        self.subscriber.on_progress(&self.progress);
+    }
+
+    /// Reports that the phase is finished
+    pub fn finish(mut self) {
+        self.progress.elapsed = self.start.elapsed();
+        self.subscriber.on_finish(&self.progress);
+    }
+}
@@ -7,6 +7,7 @@ use nimiq_genesis_builder::config::{
     GenesisAccount, GenesisHTLC, GenesisStaker, GenesisVestingContract,
 };
 use nimiq_keys::{Address, PublicKey as SchnorrPublicKey};
+use nimiq_migration_progress::{Phase, ProgressSubscriber, ProgressTracker};
 use nimiq_primitives::coin::Coin;
 use nimiq_rpc::{
     primitives::{
@@ -22,8 +23,9 @@ use crate::types::{Error, GenesisAccounts, GenesisValidator};
 
 // POW estimated block time in milliseconds
 const POW_BLOCK_TIME_MS: u64 = 60 * 1000; // 1 min
-                                          // PoS validator deposit
-const VALIDATOR_DEPOSIT: u64 = 10;
+
+/// PoS validator deposit
+pub const VALIDATOR_DEPOSIT: u64 = 10;
 
 fn pos_basic_account_from_account(pow_account: &PoWBasicAccount) -> Result<GenesisAccount, Error> {
     let address = Address::from_user_friendly_address(&pow_account.address)?;
@@ -103,7 +105,9 @@ pub async fn get_accounts(
     client: &Client,
     cutting_block: &Block,
     pos_genesis_ts: u64,
+    progress: &dyn ProgressSubscriber,
 ) -> Result<GenesisAccounts, Error> {
+    let mut progress = ProgressTracker::new(progress, Phase::Accounts, None);
     let mut genesis_accounts = GenesisAccounts {
         vesting_accounts: vec![],
         basic_accounts: vec![],
@@ -119,6 +123,7 @@ pub async fn get_accounts(
         }
         start_prefix = chunk.tail;
         log::debug!(size = chunk.nodes.len(), "Processing accounts tree chunk");
+        let chunk_size = chunk.nodes.len() as u64;
         for node in chunk.nodes {
             match node.account {
                 nimiq_rpc::primitives::Account::Basic(pow_account) => {
@@ -140,7 +145,9 @@ pub async fn get_accounts(
                 }
             }
         }
+        progress.advance(chunk_size, 0);
     }
+    progress.finish();
     Ok(genesis_accounts)
 }
 
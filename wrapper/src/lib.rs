@@ -1,70 +1,86 @@
+use std::path::Path;
+
+use nimiq_genesis_migration::profile::GenesisProfile;
+use nimiq_history_migration::database::NetworkPreset;
+use nimiq_keys::{Address, KeyPair, PrivateKey};
+use nimiq_lib::config::config_file::ConfigFile;
+use nimiq_pow_monitor::transaction::read_key_file;
 use nimiq_primitives::networks::NetworkId;
 use thiserror::Error;
 
-static TESTNET_BLOCK_WINDOWS: &BlockWindows = &BlockWindows {
-    registration_start: 2590000,
-    registration_end: 2660000,
-    pre_stake_start: 2660000,
-    pre_stake_end: 2663100,
-    block_confirmations: 10,
-};
-
-// Currently not used because there is still no network ID for PoS MainNet
-#[allow(dead_code)]
-static MAINNET_BLOCK_WINDOWS: &BlockWindows = &BlockWindows {
-    registration_start: 2590000,
-    registration_end: 2660000,
-    pre_stake_start: 2660000,
-    pre_stake_end: 2663100,
-    block_confirmations: 10,
-};
-
-/// PoW block registration window
-///
-/// The registration window is a set of blocks in the PoW chain that marks
-/// the start and end of different windows as follows:
-///
-///     1              2              3              4              5        6
-/// --- | ------------ | ------------ | ------------ | ------------ |------- |
-///
-/// 1. Validator registration window start block.
-/// 2. Validator registration window end block.
-/// 3. Pre-stake registration window start.
-/// 4. Pre-stake registration window end block. This block is also the activation
-///    window start.
-/// 5. The final block in the PoW chain that will be taken as genesis block for the
-///    PoS chain. This block must have a block number that can be an election block
-///    number in the PoS chain.
-/// 6. This is a block whose block number is a number of confirmations away from
-///    the final block described in 4.
-///
-#[derive(Debug)]
-pub struct BlockWindows {
-    /// Block number of the validator registration window start.
-    pub registration_start: u32,
-    /// Block number of the validator registration window wnd.
-    pub registration_end: u32,
-    /// Block number of the validator registration window end which is also
-    /// the pre stake registration window start.
-    pub pre_stake_start: u32,
-    /// Block number of the pre stake registration window end.
-    pub pre_stake_end: u32,
-    /// Number of confirmations after the final block needed for the PoS chain to
-    /// start.
-    pub block_confirmations: u32,
-}
-
 /// Error types that can be returned
 #[derive(Error, Debug)]
 pub enum Error {
     /// Invalid Network ID
     #[error("Invalid Network ID")]
     InvalidNetworkID(NetworkId),
+    /// Invalid genesis profile
+    #[error("Invalid genesis profile: {0}")]
+    Profile(#[from] nimiq_genesis_migration::types::Error),
+    /// The genesis profile is for a different network than the PoS client
+    #[error("Genesis profile is for {found:?} while the PoS client runs in {expected:?}")]
+    ProfileNetworkMismatch {
+        /// PoS network of the client
+        expected: NetworkId,
+        /// PoS network of the genesis profile
+        found: NetworkId,
+    },
+    /// Invalid validator key
+    #[error("Invalid validator key: {0}")]
+    ValidatorKey(#[from] nimiq_pow_monitor::types::Error),
+    /// No key for the validator address was found
+    #[error("No key found for validator address {0}")]
+    MissingValidatorKey(Address),
 }
 
-pub fn get_block_windows(network_id: NetworkId) -> Result<&'static BlockWindows, Error> {
-    match network_id {
-        NetworkId::TestAlbatross => Ok(TESTNET_BLOCK_WINDOWS),
-        _ => Err(Error::InvalidNetworkID(network_id)),
+/// Gets the genesis profile for the given PoS network
+///
+/// If `profile_file` is given, the custom profile in that file is used, which
+/// must be for the same PoS network. Otherwise, the built-in profile of the
+/// network is used, which doesn't exist for MainNet yet.
+pub fn get_genesis_profile(
+    network_id: NetworkId,
+    profile_file: Option<&Path>,
+) -> Result<GenesisProfile, Error> {
+    let profile = match profile_file {
+        Some(file) => GenesisProfile::from_file(file)?,
+        None => NetworkPreset::from_pos_network_id(network_id)
+            .and_then(GenesisProfile::for_network)
+            .ok_or(Error::InvalidNetworkID(network_id))?,
+    };
+    if profile.network.pos_network_id() != network_id {
+        return Err(Error::ProfileNetworkMismatch {
+            expected: network_id,
+            found: profile.network.pos_network_id(),
+        });
     }
+    Ok(profile)
+}
+
+/// Gets the key pair of the validator address, used to sign the ready
+/// transaction
+///
+/// If `key_file` is given, the hex encoded private key in that file is used.
+/// Otherwise, the key of the validator section of the PoS configuration file
+/// that corresponds to the validator address is used.
+pub fn get_validator_key_pair(
+    validator_address: &Address,
+    key_file: Option<&Path>,
+    config_file: &ConfigFile,
+) -> Result<KeyPair, Error> {
+    let key_pair = match key_file {
+        Some(file) => Some(read_key_file(file)?),
+        None => config_file.validator.as_ref().and_then(|validator| {
+            [&validator.fee_key, &validator.signing_key]
+                .into_iter()
+                .flatten()
+                .filter_map(|key| hex::decode(key).ok())
+                .filter_map(|key| PrivateKey::from_bytes(&key).ok())
+                .map(KeyPair::from)
+                .find(|key_pair| Address::from(&key_pair.public) == *validator_address)
+        }),
+    };
+    key_pair
+        .filter(|key_pair| Address::from(&key_pair.public) == *validator_address)
+        .ok_or_else(|| Error::MissingValidatorKey(validator_address.clone()))
 }
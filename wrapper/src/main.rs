@@ -1,17 +1,22 @@
-use std::{fs, process::exit, thread::sleep, time::Duration};
+use std::{fs, path::Path, process::exit, str::FromStr, thread::sleep, time::Duration};
 
 use clap::Parser;
 use log::info;
 use log::level_filters::LevelFilter;
 use nimiq_genesis_migration::{
     get_pos_genesis,
-    types::{PoSRegisteredAgents, PoWRegistrationWindow},
+    types::{HistorySource, PoSRegisteredAgents},
     write_pos_genesis,
 };
+use nimiq_hash::Blake2bHash;
 use nimiq_lib::config::{config::ClientConfig, config_file::ConfigFile};
+use nimiq_migration_progress::LogProgress;
 use nimiq_pow_monitor::{
-    check_validators_ready, generate_ready_tx, get_ready_txns, send_tx,
-    types::{ValidatorsReadiness, ACTIVATION_HEIGHT},
+    check_validators_ready, get_ready_txns,
+    ready::ReadyMessage,
+    send_tx,
+    transaction::{build_ready_tx, DEFAULT_FEE_PER_BYTE},
+    types::{ReadinessRule, ValidatorsReadiness, ACTIVATION_HEIGHT},
 };
 use nimiq_primitives::policy::Policy;
 use nimiq_rpc::Client;
@@ -20,7 +25,7 @@ use serde::Deserialize;
 use tracing_subscriber::{filter::Targets, layer::SubscriberExt, util::SubscriberInitExt, Layer};
 use url::Url;
 
-use nimiq_pos_wrapper::get_block_windows;
+use nimiq_pos_wrapper::{get_genesis_profile, get_validator_key_pair};
 
 /// Command line arguments for the binary
 #[derive(Parser, Debug)]
@@ -47,6 +52,20 @@ struct Args {
 #[derive(Deserialize)]
 struct Data {
     genesis: String,
+    /// Optional known history root. If set, the PoW history is not replayed.
+    history_root: Option<String>,
+    /// Optional custom genesis profile file. If not set, the built-in profile
+    /// of the PoS network is used.
+    profile: Option<String>,
+    /// Optional rule deciding whether enough validators are ready: `2f+1` of
+    /// the slots or a percentage of the slots such as `80%`. Defaults to `2f+1`.
+    readiness_rule: Option<String>,
+    /// Optional file with the hex encoded private key of the validator address.
+    /// If not set, the matching key of the validator section of the PoS
+    /// configuration file is used.
+    validator_key_file: Option<String>,
+    /// Optional fee per byte of the ready transaction in Lunas
+    fee_per_byte: Option<u64>,
 }
 
 fn initialize_logging() {
@@ -132,6 +151,19 @@ async fn main() {
         }
     };
 
+    let readiness_rule = match settings
+        .readiness_rule
+        .as_deref()
+        .map(ReadinessRule::from_str)
+        .transpose()
+    {
+        Ok(readiness_rule) => readiness_rule.unwrap_or_default(),
+        Err(error) => {
+            log::error!(?error, "Invalid readiness rule in the settings file");
+            exit(1);
+        }
+    };
+
     let validator_address = if let Some(validator_settings) = config.validator {
         validator_settings.validator_address
     } else {
@@ -141,6 +173,18 @@ async fn main() {
 
     info!("This is our validator address: {}", validator_address);
 
+    let validator_key_pair = match get_validator_key_pair(
+        &validator_address,
+        settings.validator_key_file.as_deref().map(Path::new),
+        &config_file,
+    ) {
+        Ok(key_pair) => key_pair,
+        Err(error) => {
+            log::error!(?error, "Couldn't get the validator key");
+            exit(1);
+        }
+    };
+
     // Create DB environment
     let env = match config.storage.database(
         config.network_id,
@@ -168,13 +212,17 @@ async fn main() {
         Client::new(url)
     };
 
-    let block_windows = match get_block_windows(config.network_id) {
-        Ok(block_windows) => block_windows,
+    let profile = match get_genesis_profile(
+        config.network_id,
+        settings.profile.as_deref().map(Path::new),
+    ) {
+        Ok(profile) => profile,
         Err(error) => {
-            log::error!(?error, "Couldn't get block windows");
+            log::error!(?error, "Couldn't get genesis profile");
             std::process::exit(1);
         }
     };
+    let block_windows = &profile.block_windows;
 
     loop {
         let status = client.consensus().await.unwrap();
@@ -248,7 +296,7 @@ async fn main() {
     }
 
     let mut reported_ready = false;
-    loop {
+    let candidate = loop {
         let current_height = client.block_number().await.unwrap();
         info!(current_height);
 
@@ -259,6 +307,10 @@ async fn main() {
             previous_election_block = ACTIVATION_HEIGHT;
         }
 
+        // Ready messages target the next election block as candidate. The genesis hash is
+        // not known yet at this point, so it is not included.
+        let ready_message = ReadyMessage::new(next_election_block, None);
+
         if !reported_ready {
             // Obtain all the transactions that we have sent previously.
             // TODO: We need to check that this validator is part of the list of the registered validators!
@@ -266,6 +318,7 @@ async fn main() {
                 &client,
                 validator_address.to_user_friendly_address(),
                 previous_election_block..next_election_block,
+                &ready_message,
             )
             .await;
 
@@ -276,9 +329,19 @@ async fn main() {
                     "We didn't find a ready transaction from our validator in this window"
                 );
                 // Report we are ready to the Nimiq PoW chain:
-                let transaction = generate_ready_tx(validator_address.to_user_friendly_address());
+                log::info!(
+                    election_block = ready_message.election_block,
+                    "Sending ready transaction"
+                );
+                let transaction = build_ready_tx(
+                    &validator_key_pair,
+                    &ready_message,
+                    current_height,
+                    profile.network.pow_network_id(),
+                    settings.fee_per_byte.unwrap_or(DEFAULT_FEE_PER_BYTE),
+                );
 
-                match send_tx(&client, transaction).await {
+                match send_tx(&client, &transaction).await {
                     Ok(_) => reported_ready = true,
                     Err(_) => exit(1),
                 }
@@ -289,17 +352,28 @@ async fn main() {
         }
 
         // Check if we have enough validators ready at this point
-        let validators_status = check_validators_ready(&client, validators.clone()).await;
+        let validators_status =
+            check_validators_ready(&client, validators.clone(), readiness_rule, &ready_message)
+                .await;
         match validators_status {
-            ValidatorsReadiness::NotReady(stake) => {
-                info!(stake_ready = %stake, "Not enough validators are ready yet",);
+            ValidatorsReadiness::NotReady(slots) => {
+                info!(
+                    slots_ready = slots.ready,
+                    total_slots = slots.total,
+                    threshold = slots.threshold,
+                    %readiness_rule,
+                    "Not enough validators are ready yet",
+                );
             }
-            ValidatorsReadiness::Ready(stake) => {
+            ValidatorsReadiness::Ready(slots) => {
                 info!(
-                    stake_ready = %stake,
+                    slots_ready = slots.ready,
+                    total_slots = slots.total,
+                    threshold = slots.threshold,
+                    %readiness_rule,
                     "Enough validators are ready to start the PoS chain",
                 );
-                break;
+                break ready_message.election_block;
             }
         }
 
@@ -310,11 +384,9 @@ async fn main() {
         {
             reported_ready = false;
         }
-    }
-
-    // Now that we have enough validators ready, we need to pick the next election block candidate
-    let candidate = Policy::election_block_after(client.block_number().await.unwrap());
+    };
 
+    // The election block candidate is the one the ready validators agreed on
     info!(next_election_candidate = candidate);
 
     loop {
@@ -333,22 +405,29 @@ async fn main() {
     let block = client.get_block_by_number(candidate, false).await.unwrap();
 
     // Start the genesis generation process
-    let pow_registration_window = PoWRegistrationWindow {
-        pre_stake_start: block_windows.pre_stake_start,
-        pre_stake_end: block_windows.pre_stake_end,
-        validator_start: block_windows.registration_start,
-        final_block: block.hash,
-        confirmations: block_windows.block_confirmations,
+    let pow_registration_window = profile.registration_window(block.hash);
+
+    let history = match &settings.history_root {
+        Some(history_root) => match Blake2bHash::from_str(history_root) {
+            Ok(history_root) => HistorySource::Precomputed(history_root),
+            Err(error) => {
+                log::error!(?error, "Invalid history root in the settings file");
+                exit(1);
+            }
+        },
+        None => HistorySource::Build(env),
     };
 
     let genesis_config = match get_pos_genesis(
         &client,
         &pow_registration_window,
-        env,
+        history,
+        &profile,
         Some(PoSRegisteredAgents {
             validators,
             stakers,
         }),
+        &LogProgress::new(Duration::from_secs(60)),
     )
     .await
     {